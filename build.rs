@@ -1,5 +1,6 @@
 fn main() {
   cc::Build::new()
       .file("sqlite/sqlite3.c")  // Ruta al archivo fuente
+      .define("SQLITE_ENABLE_FTS5", None)  // Búsqueda de texto completo
       .compile("sqlite3");       // Nombre de la biblioteca generada
 }
\ No newline at end of file
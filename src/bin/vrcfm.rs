@@ -0,0 +1,331 @@
+//! `vrcfm`: la biblioteca desde la línea de comandos, con la misma base y configuración que la
+//! aplicación. Pensado para scripts y para usarlo por SSH donde no hay escritorio.
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use vrcfm::config::{AppConfig, LibraryPaths};
+use vrcfm::database::Database;
+use vrcfm::models::{IndexedFile, Item};
+use vrcfm::query::Query;
+use vrcfm::scanner;
+use vrcfm::versions;
+use vrcfm::worker::{CollisionPolicy, Task, Worker, WorkerEvent};
+
+#[derive(Parser)]
+#[command(name = "vrcfm", version, about = "Administrador de archivos VRC sin interfaz gráfica")]
+struct Cli {
+    /// Carpeta principal de la biblioteca; por defecto, la que usa la aplicación
+    #[arg(long, short = 'L', global = true)]
+    library: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Sincroniza las categorías con las carpetas e indexa la carpeta principal
+    Scan {
+        /// Calcular también el hash del contenido de las carpetas
+        #[arg(long)]
+        hash: bool,
+    },
+    /// Busca carpetas de item (misma sintaxis que el buscador de la aplicación)
+    Search {
+        /// p. ej. `tag:toon -tag:nsfw size:>50MB`
+        #[arg(required = true)]
+        query: Vec<String>,
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Una línea JSON por resultado
+        #[arg(long)]
+        json: bool,
+    },
+    /// Tags de las carpetas de item
+    #[command(subcommand)]
+    Tag(TagCommand),
+    /// Árbol de categorías
+    #[command(subcommand)]
+    Category(CategoryCommand),
+    /// Copia archivos a la carpeta de un item nuevo (o como versión nueva de uno que ya existe)
+    Import {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Ruta de la categoría, p. ej. `Avatars/Manuka`
+        #[arg(long)]
+        category: String,
+        /// Nombre del item; por defecto el del primer archivo sin la versión
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Imagen de referencia si el paquete no trae vista previa
+        #[arg(long)]
+        image: Option<PathBuf>,
+        #[arg(long)]
+        notes: Option<String>,
+        /// Notas de la versión
+        #[arg(long)]
+        changelog: Option<String>,
+        /// Qué hacer si ya existe un archivo con el mismo nombre
+        #[arg(long, value_enum, default_value_t = OnConflict::Rename)]
+        on_conflict: OnConflict,
+    },
+    /// Vuelca categorías, tags, carpetas indexadas e items como JSON
+    Export {
+        /// Archivo de salida; por defecto la salida estándar
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Abre la biblioteca movida a otra carpeta, sin volver a importar nada, y la deja como la que
+    /// usa la aplicación
+    Relocate { new_root: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum TagCommand {
+    /// Lista los tags y cuántas carpetas usan cada uno
+    Ls,
+    /// Añade tags a una carpeta de item (ruta completa o relativa a la carpeta principal)
+    Add { folder: String, #[arg(required = true)] tags: Vec<String> },
+    /// Quita tags de una carpeta de item
+    Rm { folder: String, #[arg(required = true)] tags: Vec<String> },
+}
+
+#[derive(Subcommand)]
+enum CategoryCommand {
+    Ls,
+    /// Crea la carpeta de la categoría (y las de sus padres si faltan)
+    Create { path: String },
+    /// Renombra la categoría y su carpeta
+    Rename { path: String, new_name: String },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OnConflict {
+    Rename,
+    Overwrite,
+    Skip,
+}
+
+impl From<OnConflict> for CollisionPolicy {
+    fn from(value: OnConflict) -> Self {
+        match value {
+            OnConflict::Rename => CollisionPolicy::Rename,
+            OnConflict::Overwrite => CollisionPolicy::Overwrite,
+            OnConflict::Skip => CollisionPolicy::Skip,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Export {
+    categories: Vec<String>,
+    tags: Vec<String>,
+    folders: Vec<IndexedFile>,
+    items: Vec<Item>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let root = match (&cli.command, cli.library) {
+        (Command::Relocate { new_root }, _) => {
+            fs::canonicalize(new_root).with_context(|| format!("no existe {}", new_root.display()))?
+        }
+        (_, Some(root)) => fs::canonicalize(&root).with_context(|| format!("no existe {}", root.display()))?,
+        (_, None) => {
+            let config = AppConfig::load().context("no hay carpeta principal configurada; abre la aplicación una vez para elegirla o usa --library")?;
+            PathBuf::from(config.root_path)
+        }
+    };
+    let library = LibraryPaths::new(&root);
+    let opened = match &cli.command {
+        Command::Relocate { .. } => Database::open_moved_library(&library),
+        _ => Database::open_library(&library),
+    };
+    let opened = opened.context("no se pudo abrir la biblioteca")?;
+    let db = opened.db;
+    if let Some(backup) = opened.backup {
+        eprintln!("Base de datos actualizada; copia anterior en {}", backup.display());
+    }
+    if let Some(relocation) = &opened.relocation {
+        eprintln!("Biblioteca reubicada en {} ({} carpetas encontradas)", root.display(), relocation.found);
+        for path in &relocation.missing {
+            eprintln!("Falta: {}", path);
+        }
+    }
+
+    match cli.command {
+        Command::Scan { hash } => {
+            sync_categories(&db, &root)?;
+            let event = run_task(&library, Task::Scan { root, hash_contents: hash })?;
+            if let WorkerEvent::ScanFinished(summary) = event {
+                for warning in &summary.warnings {
+                    eprintln!("Aviso: {}", warning);
+                }
+                println!(
+                    "{} nuevas, {} modificadas, {} eliminadas, {} sin cambios",
+                    summary.new, summary.modified, summary.missing, summary.unchanged
+                );
+            }
+        }
+        Command::Search { query, limit, json } => {
+            let query = Query::parse(&query.join(" "))?;
+            let hits = db.files().search(&query, 0, limit)?;
+            let mut out = std::io::stdout().lock();
+            for hit in hits {
+                if json {
+                    writeln!(out, "{}", serde_json::to_string(&hit.file)?)?;
+                } else {
+                    let version = hit.file.version.map(|v| format!(" v{}", v)).unwrap_or_default();
+                    writeln!(out, "{}{}\t{}", hit.file.path, version, hit.file.tags.join(", "))?;
+                }
+            }
+        }
+        Command::Tag(TagCommand::Ls) => {
+            let usage = db.tags().usage_counts()?;
+            for tag in db.tags().get_tags()? {
+                println!("{}\t{}", tag, usage.get(&tag).copied().unwrap_or(0));
+            }
+        }
+        Command::Tag(TagCommand::Add { folder, tags }) => {
+            let mut file = find_folder(&db, &root, &folder)?;
+            for tag in tags {
+                if !file.tags.contains(&tag) {
+                    file.tags.push(tag);
+                }
+            }
+            db.files().set_file_tags(&file.path, &file.tags)?;
+        }
+        Command::Tag(TagCommand::Rm { folder, tags }) => {
+            let mut file = find_folder(&db, &root, &folder)?;
+            file.tags.retain(|t| !tags.contains(t));
+            db.files().set_file_tags(&file.path, &file.tags)?;
+        }
+        Command::Category(CategoryCommand::Ls) => {
+            for cat in db.categories().get_categories()? {
+                println!("{}", cat.path);
+            }
+        }
+        Command::Category(CategoryCommand::Create { path }) => {
+            let path = path.trim_matches('/');
+            if path.is_empty() || !path.split('/').all(scanner::is_valid_folder_name) {
+                bail!("nombre de categoría no válido: '{}'", path);
+            }
+            fs::create_dir_all(scanner::category_dir(&root, path)).context("error creando la carpeta de la categoría")?;
+            db.categories().ensure_path(path)?;
+        }
+        Command::Category(CategoryCommand::Rename { path, new_name }) => {
+            rename_category(&db, path.trim_matches('/'), &new_name)?;
+        }
+        Command::Import { files, category, name, tags, image, notes, changelog, on_conflict } => {
+            if !db.categories().get_categories()?.iter().any(|c| c.path == category) {
+                bail!("no existe la categoría '{}'", category);
+            }
+            let name = match name {
+                Some(name) => name.trim().to_string(),
+                None => versions::base_name(&files[0]).context("no se pudo deducir el nombre del item; usa --name")?,
+            };
+            let item = Item { name: name.clone(), category: category.clone(), tags, notes, ..Default::default() };
+            run_task(&library, Task::ImportItem {
+                item: Box::new(item),
+                root: root.clone(),
+                data_srcs: files,
+                image_src: image,
+                use_package_preview: true,
+                collision: on_conflict.into(),
+                changelog,
+            })?;
+            println!("Importado '{}' en {}", name, category);
+        }
+        Command::Export { output } => {
+            let export = Export {
+                categories: db.categories().get_categories()?.into_iter().map(|c| c.path).collect(),
+                tags: db.tags().get_tags()?,
+                folders: db.files().get_files()?,
+                items: db.items().get_items()?,
+            };
+            let json = serde_json::to_string_pretty(&export)?;
+            match output {
+                Some(path) => fs::write(&path, json).with_context(|| format!("error escribiendo {}", path.display()))?,
+                None => writeln!(std::io::stdout(), "{}", json)?,
+            }
+        }
+        Command::Relocate { .. } => {
+            // Al abrirla ya se reubicó si hacía falta; solo queda recordarla
+            let config = AppConfig { root_path: root.display().to_string() };
+            config.save().context("no se pudo guardar la configuración")?;
+            if opened.relocation.is_none() {
+                println!("La biblioteca ya estaba en {}", config.root_path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ejecuta una tarea del hilo de trabajo esperando a que termine; el progreso va a stderr
+fn run_task(library: &LibraryPaths, task: Task) -> Result<WorkerEvent> {
+    let worker = Worker::spawn_headless(&library.db(), task);
+    let mut last = (String::new(), u64::MAX);
+    while let Some(event) = worker.recv() {
+        match event {
+            WorkerEvent::Progress { label, done, total } => {
+                let percent = (done * 100).checked_div(total).unwrap_or(0);
+                if (&label, percent) != (&last.0, last.1) {
+                    eprint!("\r\x1b[K{} {}%", label, percent);
+                    last = (label, percent);
+                }
+            }
+            WorkerEvent::Warning(message) => eprintln!("\r\x1b[KAviso: {}", message),
+            WorkerEvent::Failed(e) => {
+                eprintln!();
+                bail!(e);
+            }
+            WorkerEvent::Cancelled => bail!("operación cancelada"),
+            event => {
+                eprint!("\r\x1b[K");
+                return Ok(event);
+            }
+        }
+    }
+    bail!("el hilo de trabajo terminó sin responder")
+}
+
+/// Registra las carpetas de categoría nuevas y quita las que ya no existen, como al abrir la aplicación
+fn sync_categories(db: &Database, root: &Path) -> Result<()> {
+    if !root.is_dir() {
+        bail!("la carpeta principal no existe: {}", root.display());
+    }
+    let known: Vec<String> = db.categories().get_categories()?.into_iter().map(|c| c.path).collect();
+    db.categories().sync_paths(&scanner::category_folders(root, &known))?;
+    Ok(())
+}
+
+/// Carpeta indexada por su ruta, tal cual o relativa a la carpeta principal
+fn find_folder(db: &Database, root: &Path, folder: &str) -> Result<IndexedFile> {
+    let relative = folder.split('/').fold(root.to_path_buf(), |dir, name| dir.join(name));
+    for path in [folder.to_string(), relative.display().to_string()] {
+        if let Some(file) = db.files().get_file(&path)? {
+            return Ok(file);
+        }
+    }
+    bail!("'{}' no es una carpeta de item indexada (¿falta `vrcfm scan`?)", folder)
+}
+
+/// Renombra la carpeta de la categoría y actualiza la base (ver [`Database::move_category`])
+fn rename_category(db: &Database, path: &str, new_name: &str) -> Result<()> {
+    let categories = db.categories().get_categories()?;
+    let current = categories.iter().find(|c| c.path == path).with_context(|| format!("no existe la categoría '{}'", path))?;
+    db.move_category(current.id, current.parent_id, new_name)?;
+    Ok(())
+}
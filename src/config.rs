@@ -0,0 +1,140 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Carpeta (oculta) dentro de la carpeta principal con la base, las miniaturas y los ajustes de la
+/// biblioteca; así la biblioteca se copia a otro equipo como una sola carpeta
+pub const LIBRARY_DIR: &str = ".vrcfm";
+pub const DB_FILE: &str = "files.db";
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+pub const SETTINGS_FILE: &str = "settings.json";
+pub const CONFIG_FILE: &str = "config.json";
+pub const LOG_FILE: &str = "vrcfm.log";
+
+/// Carpeta de configuración de la aplicación (`~/.config/vrcfm`, `%APPDATA%\vrcfm`, ...). Si el
+/// sistema no tiene una se usa la carpeta de trabajo.
+pub fn app_dir() -> PathBuf {
+    dirs::config_dir().map(|dir| dir.join("vrcfm")).unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Configuración de la aplicación, compartida por la interfaz y `vrcfm`: qué biblioteca abrir
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AppConfig {
+    pub root_path: String,
+}
+
+impl AppConfig {
+    pub fn path() -> PathBuf {
+        app_dir().join(CONFIG_FILE)
+    }
+
+    /// La configuración guardada, si existe y tiene carpeta principal. Si aún no se guardó en
+    /// [`app_dir`] se toma el `config.json` de la carpeta de trabajo que usaban las versiones anteriores.
+    pub fn load() -> Option<AppConfig> {
+        let config = read_json::<AppConfig>(&Self::path()).or_else(|| read_json(Path::new(CONFIG_FILE)))?;
+        (!config.root_path.is_empty()).then_some(config)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        fs::create_dir_all(app_dir())?;
+        fs::write(Self::path(), serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Ajustes de una biblioteca, en `.vrcfm/settings.json`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LibrarySettings {
+    /// Días que una categoría eliminada se conserva en la papelera
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+}
+
+pub fn default_trash_retention_days() -> u32 {
+    30
+}
+
+impl Default for LibrarySettings {
+    fn default() -> Self {
+        Self { trash_retention_days: default_trash_retention_days() }
+    }
+}
+
+impl LibrarySettings {
+    pub fn load(library: &LibraryPaths) -> Self {
+        read_json(&library.settings()).unwrap_or_default()
+    }
+
+    pub fn save(&self, library: &LibraryPaths) -> io::Result<()> {
+        fs::write(library.settings(), serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Dónde se guarda cada cosa de la biblioteca con carpeta principal `root`
+#[derive(Debug, Clone)]
+pub struct LibraryPaths {
+    root: PathBuf,
+}
+
+impl LibraryPaths {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.root.join(LIBRARY_DIR)
+    }
+
+    pub fn db(&self) -> String {
+        self.dir().join(DB_FILE).display().to_string()
+    }
+
+    pub fn thumbnails(&self) -> PathBuf {
+        self.dir().join(THUMBNAIL_DIR)
+    }
+
+    pub fn settings(&self) -> PathBuf {
+        self.dir().join(SETTINGS_FILE)
+    }
+
+    /// Crea `.vrcfm/`. Si es la biblioteca que usaban las versiones anteriores, copia ahí la base y
+    /// los ajustes que guardaban en la carpeta de trabajo (los originales no se tocan).
+    pub fn prepare(&self) -> io::Result<()> {
+        if !self.root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} no existe", self.root.display())));
+        }
+        fs::create_dir_all(self.dir())?;
+
+        let Some(legacy) = read_json::<LegacyConfig>(Path::new(CONFIG_FILE)) else {
+            return Ok(());
+        };
+        if Path::new(&legacy.root_path) != self.root {
+            return Ok(());
+        }
+        if !Path::new(&self.db()).exists() && Path::new(DB_FILE).is_file() {
+            fs::copy(DB_FILE, self.db())?;
+        }
+        if !self.settings().exists() {
+            LibrarySettings { trash_retention_days: legacy.trash_retention_days }.save(self)?;
+        }
+        Ok(())
+    }
+}
+
+/// `config.json` de las versiones que guardaban todo en la carpeta de trabajo
+#[derive(Deserialize)]
+struct LegacyConfig {
+    root_path: String,
+    #[serde(default = "default_trash_retention_days")]
+    trash_retention_days: u32,
+}
+
+/// Un archivo JSON que no existe o no se entiende cuenta como ausente
+fn read_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
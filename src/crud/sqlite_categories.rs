@@ -1,50 +1,194 @@
-use rusqlite::{params, Connection};
-use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use crate::database::savepoint;
+use crate::error::{StoreError, StoreResult};
+use crate::library::LibraryRoot;
+use crate::models::Category;
 
-pub struct CategoryStore {
-  conn: Connection,
+/// Recorre el árbol desde las raíces calculando la ruta de cada categoría. `sort_key` deja a
+/// cada categoría justo detrás de su padre y ordena a los hermanos por `sort_order` y nombre.
+pub(crate) const TREE_CTE: &str = r#"
+  WITH RECURSIVE tree(id, parent_id, name, sort_order, path, sort_key) AS (
+    SELECT id, parent_id, name, sort_order, name, printf('%08d', sort_order) || name
+    FROM categories WHERE parent_id IS NULL
+    UNION ALL
+    SELECT c.id, c.parent_id, c.name, c.sort_order, tree.path || '/' || c.name,
+           tree.sort_key || char(1) || printf('%08d', c.sort_order) || c.name
+    FROM categories c JOIN tree ON c.parent_id = tree.id
+  )
+"#;
+
+pub struct CategoryStore<'a> {
+  conn: &'a Connection,
+  root: &'a LibraryRoot,
 }
 
-impl CategoryStore {
-  pub fn new(conn: Connection) -> Self {
-    Self { conn }
+impl<'a> CategoryStore<'a> {
+  pub fn new(conn: &'a Connection, root: &'a LibraryRoot) -> Self {
+    Self { conn, root }
   }
 
-  pub fn init(&self) -> Result<()> {
-    self.conn.execute_batch(
+  /// INSERT - crea una categoría al final de sus hermanas, devuelve el id nuevo
+  pub fn insert_category(&self, parent_id: Option<i64>, name: &str) -> StoreResult<i64> {
+    self.conn.execute(
       r#"
-        CREATE TABLE IF NOT EXISTS categories (name TEXT PRIMARY KEY);
+        INSERT INTO categories (parent_id, name, sort_order)
+        SELECT ?1, ?2, ifnull(max(sort_order), 0) + 1 FROM categories WHERE parent_id IS ?1
       "#,
+      params![parent_id, name],
     )?;
-    Ok(())
+    Ok(self.conn.last_insert_rowid())
   }
 
-  pub fn insert_category(&self, name: &str) -> Result<()> {
-    self.conn.execute(
-      "INSERT OR IGNORE INTO categories (name) VALUES (?1)",
-      params![name],
-    )?;
-    Ok(())
+  /// Devuelve el id de la categoría con esa ruta (`Avatars/Base Models`), creando las que falten
+  pub fn ensure_path(&self, path: &str) -> StoreResult<i64> {
+    let mut parent = None;
+    for name in path.split('/').filter(|n| !n.is_empty()) {
+      let existing: Option<i64> = self.conn
+        .query_row(
+          "SELECT id FROM categories WHERE parent_id IS ?1 AND name = ?2",
+          params![parent, name],
+          |r| r.get(0),
+        )
+        .optional()?;
+      parent = Some(match existing {
+        Some(id) => id,
+        None => self.insert_category(parent, name)?,
+      });
+    }
+    parent.ok_or_else(|| StoreError::NotFound("categoría vacía".into()))
   }
 
-  pub fn update_category(&self, old_name: &str, new_name: &str) -> Result<()> {
-    self.conn.execute(
-      "UPDATE categories SET name = ?1 WHERE name = ?2",
-      params![new_name, old_name],
-    )?;
-    Ok(())
+  /// Deja en la tabla las categorías de `on_disk` (las rutas de las carpetas que hay en disco):
+  /// registra las que falten, elimina las que ya no existen y devuelve a su categoría los items
+  /// que se quedaron sin ella cuando su carpeta vuelve a aparecer
+  pub fn sync_paths(&self, on_disk: &[String]) -> StoreResult<()> {
+    savepoint(self.conn, || {
+      for path in on_disk {
+        self.ensure_path(path)?;
+      }
+      for cat in self.get_categories()? {
+        if !on_disk.contains(&cat.path) {
+          self.delete_category(cat.id)?;
+        }
+      }
+      let sql = format!(
+        "UPDATE items SET category_id = ({TREE_CTE} SELECT id FROM tree WHERE tree.path = items.category)
+         WHERE category_id IS NULL"
+      );
+      self.conn.execute(&sql, [])?;
+      Ok(())
+    })
+  }
+
+  /// Cambia el padre y/o el nombre de una categoría y reescribe en una sola transacción todas las
+  /// rutas guardadas bajo `old_dir` (índice de carpetas, contenido de paquetes, items y sus
+  /// archivos) para que empiecen por `new_dir`. Si algo falla no se cambia nada.
+  pub fn move_category(&self, id: i64, new_parent: Option<i64>, new_name: &str, old_dir: &str, new_dir: &str) -> StoreResult<()> {
+    savepoint(self.conn, || {
+      let sql = format!("{TREE_CTE} SELECT id, parent_id, name, sort_order, path FROM tree WHERE id = ?1");
+      let current = self.conn
+        .query_row(&sql, params![id], Self::row_to_category)
+        .optional()?
+        .ok_or_else(|| StoreError::NotFound(format!("categoría {}", id)))?;
+
+      if let Some(parent) = new_parent {
+        let sql = format!("{TREE_CTE} SELECT path FROM tree WHERE id = ?1");
+        let parent_path: String = self.conn
+          .query_row(&sql, params![parent], |r| r.get(0))
+          .optional()?
+          .ok_or_else(|| StoreError::NotFound(format!("categoría {}", parent)))?;
+        if parent_path == current.path || parent_path.starts_with(&format!("{}/", current.path)) {
+          return Err(StoreError::Constraint("no se puede mover una categoría dentro de sí misma".into()));
+        }
+      }
+
+      if new_parent == current.parent_id {
+        self.conn.execute("UPDATE categories SET name = ?1 WHERE id = ?2", params![new_name, id])?;
+      } else {
+        self.conn.execute(
+          r#"
+            UPDATE categories
+            SET parent_id = ?1, name = ?2,
+                sort_order = (SELECT ifnull(max(sort_order), 0) + 1 FROM categories WHERE parent_id IS ?1)
+            WHERE id = ?3
+          "#,
+          params![new_parent, new_name, id],
+        )?;
+      }
+
+      let sql = format!("{TREE_CTE} SELECT path FROM tree WHERE id = ?1");
+      let new_path: String = self.conn.query_row(&sql, params![id], |r| r.get(0))?;
+
+      // Se compara con substr en vez de LIKE para que `_` o `%` en los nombres no sean comodines
+      self.conn.execute(
+        r#"
+          UPDATE items SET category = ?2 || substr(category, length(?1) + 1)
+          WHERE category = ?1 OR substr(category, 1, length(?1) + 1) = ?1 || '/'
+        "#,
+        params![current.path, new_path],
+      )?;
+
+      // Dentro de la biblioteca las rutas se guardan relativas y con `/`; las absolutas que queden
+      // usan el separador del sistema
+      let (old_dir, new_dir) = (self.root.relative(old_dir), self.root.relative(new_dir));
+      let sep = std::path::MAIN_SEPARATOR_STR;
+      for (table, column) in [
+        // `file_tags.file_path` y los `item_path` siguen a `files.path` en cascada
+        ("files", "path"),
+        ("package_assets", "package"),
+        ("items", "file_path"),
+        ("items", "image_path"),
+        ("item_files", "path"),
+        ("file_hashes", "path"),
+        ("file_links", "path"),
+        ("file_links", "target"),
+        ("item_versions", "file_path"),
+      ] {
+        self.conn.execute(
+          &format!(
+            "UPDATE {table} SET {column} = ?2 || substr({column}, length(?1) + 1)
+             WHERE {column} = ?1 OR substr({column}, 1, length(?1) + 1) IN (?1 || '/', ?1 || ?3)"
+          ),
+          params![old_dir, new_dir, sep],
+        )?;
+      }
+
+      Ok(())
+    })
+  }
+
+  /// Intercambia la posición de dos categorías hermanas
+  pub fn swap_sort_order(&self, a: i64, b: i64) -> StoreResult<()> {
+    savepoint(self.conn, || {
+      let order_a: i64 = self.conn.query_row("SELECT sort_order FROM categories WHERE id = ?1", params![a], |r| r.get(0))?;
+      let order_b: i64 = self.conn.query_row("SELECT sort_order FROM categories WHERE id = ?1", params![b], |r| r.get(0))?;
+      self.conn.execute("UPDATE categories SET sort_order = ?1 WHERE id = ?2", params![order_b, a])?;
+      self.conn.execute("UPDATE categories SET sort_order = ?1 WHERE id = ?2", params![order_a, b])?;
+      Ok(())
+    })
   }
 
-  pub fn delete_category(&self, name: &str) -> Result<()> {
-    self.conn.execute("DELETE FROM categories WHERE name = ?1",
-      params![name],
+  /// DELETE - elimina la categoría y todas sus descendientes
+  pub fn delete_category(&self, id: i64) -> StoreResult<()> {
+    self.conn.execute(
+      r#"
+        WITH RECURSIVE sub(id) AS (
+          SELECT ?1
+          UNION ALL
+          SELECT c.id FROM categories c JOIN sub ON c.parent_id = sub.id
+        )
+        DELETE FROM categories WHERE id IN sub
+      "#,
+      params![id],
     )?;
     Ok(())
   }
 
-  pub fn get_categories(&self) -> Result<Vec<String>> {
-    let mut stmt = self.conn.prepare("SELECT name FROM categories ORDER BY name")?;
-    let rows = stmt.query_map([], |r| Ok(r.get::<_, String>(0)?))?;
+  /// SELECT - el árbol completo en orden de visualización (cada padre antes que sus hijas)
+  pub fn get_categories(&self) -> StoreResult<Vec<Category>> {
+    let sql = format!("{TREE_CTE} SELECT id, parent_id, name, sort_order, path FROM tree ORDER BY sort_key");
+    let mut stmt = self.conn.prepare(&sql)?;
+    let rows = stmt.query_map([], Self::row_to_category)?;
 
     let mut v = Vec::new();
     for r in rows {
@@ -52,4 +196,14 @@ impl CategoryStore {
     }
     Ok(v)
   }
-}
\ No newline at end of file
+
+  fn row_to_category(r: &Row) -> rusqlite::Result<Category> {
+    Ok(Category {
+      id: r.get(0)?,
+      parent_id: r.get(1)?,
+      name: r.get(2)?,
+      sort_order: r.get(3)?,
+      path: r.get(4)?,
+    })
+  }
+}
@@ -0,0 +1,229 @@
+use crate::database::savepoint;
+use crate::crud::sqlite_categories::TREE_CTE;
+use crate::error::StoreResult;
+use crate::library::LibraryRoot;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::models::{FileRole, Item, ItemFile};
+
+pub struct ItemStore<'a> {
+  conn: &'a Connection,
+  root: &'a LibraryRoot,
+}
+
+impl<'a> ItemStore<'a> {
+  pub fn new(conn: &'a Connection, root: &'a LibraryRoot) -> Self {
+    Self { conn, root }
+  }
+
+  /// INSERT - crear un item y asociar sus tags, devuelve el id nuevo
+  pub fn insert_item(&self, item: &Item) -> StoreResult<i64> {
+    savepoint(self.conn, || {
+      self.conn.execute(
+        &format!(
+          r#"
+            INSERT INTO items (name, category, category_id, file_path, image_path, notes)
+            VALUES (?1, ?2, ({TREE_CTE} SELECT id FROM tree WHERE path = ?2), ?3, ?4, ?5)
+          "#
+        ),
+        params![
+          item.name,
+          item.category,
+          self.root.relative(&item.file_path),
+          item.image_path.as_deref().map(|p| self.root.relative(p)),
+          item.notes
+        ],
+      )?;
+      let id = self.conn.last_insert_rowid();
+      self.set_item_tags(id, &item.tags)?;
+      Ok(id)
+    })
+  }
+
+  /// SELECT - obtener un item por id
+  pub fn get_item(&self, id: i64) -> StoreResult<Option<Item>> {
+    let item = self.conn
+      .query_row(
+        "SELECT id, name, category, file_path, image_path, created_at, updated_at, notes FROM items WHERE id = ?1",
+        params![id],
+        |r| self.row_to_item(r),
+      )
+      .optional()?;
+
+    match item {
+      Some(mut item) => {
+        item.tags = self.get_item_tags(item.id)?;
+        Ok(Some(item))
+      }
+      None => Ok(None),
+    }
+  }
+
+  /// SELECT - obtener todos los items
+  pub fn get_items(&self) -> StoreResult<Vec<Item>> {
+    self.query_items(
+      "SELECT id, name, category, file_path, image_path, created_at, updated_at, notes FROM items ORDER BY name",
+      params![],
+    )
+  }
+
+  /// SELECT - obtener los items de una categoría
+  pub fn get_items_by_category(&self, category: &str) -> StoreResult<Vec<Item>> {
+    self.query_items(
+      "SELECT id, name, category, file_path, image_path, created_at, updated_at, notes FROM items WHERE category = ?1 ORDER BY name",
+      params![category],
+    )
+  }
+
+  /// SELECT - item cuyo archivo principal está en la carpeta `item_dir`, si hay alguno
+  pub fn find_item_in_folder(&self, item_dir: &str) -> StoreResult<Option<i64>> {
+    let id = self.conn
+      .query_row(
+        r#"
+          SELECT id FROM items
+          WHERE substr(file_path, 1, length(?1) + 1) IN (?1 || '/', ?1 || '\')
+          ORDER BY id LIMIT 1
+        "#,
+        params![self.root.relative(item_dir)],
+        |r| r.get(0),
+      )
+      .optional()?;
+    Ok(id)
+  }
+
+  /// SELECT - buscar items por nombre o por tag
+  pub fn search_items(&self, query: &str) -> StoreResult<Vec<Item>> {
+    self.query_items(
+      r#"
+        SELECT DISTINCT i.id, i.name, i.category, i.file_path, i.image_path, i.created_at, i.updated_at, i.notes
+        FROM items i
+        LEFT JOIN item_tags it ON it.item_id = i.id
+        LEFT JOIN tags t ON t.id = it.tag_id
+        WHERE i.name LIKE ?1 OR t.name LIKE ?1
+        ORDER BY i.name
+      "#,
+      params![format!("%{}%", query)],
+    )
+  }
+
+  /// UPDATE - editar un item existente y reemplazar sus tags
+  pub fn update_item(&self, item: &Item) -> StoreResult<()> {
+    savepoint(self.conn, || {
+      self.conn.execute(
+        &format!(
+          r#"
+            UPDATE items
+            SET name = ?1, category = ?2, category_id = ({TREE_CTE} SELECT id FROM tree WHERE path = ?2),
+                file_path = ?3, image_path = ?4, notes = ?5, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?6
+          "#
+        ),
+        params![
+          item.name,
+          item.category,
+          self.root.relative(&item.file_path),
+          item.image_path.as_deref().map(|p| self.root.relative(p)),
+          item.notes,
+          item.id
+        ],
+      )?;
+      self.set_item_tags(item.id, &item.tags)
+    })
+  }
+
+  /// DELETE - eliminar un item; sus relaciones con tags y archivos se borran en cascada
+  pub fn delete_item(&self, id: i64) -> StoreResult<()> {
+    self.conn.execute("DELETE FROM items WHERE id = ?1", params![id])?;
+    Ok(())
+  }
+
+  /// Reemplaza los tags de un item; los que no existan se crean, igual que en `SqliteStore::set_file_tags`
+  pub fn set_item_tags(&self, item_id: i64, tags: &[String]) -> StoreResult<()> {
+    savepoint(self.conn, || {
+      self.conn.execute("DELETE FROM item_tags WHERE item_id = ?1", params![item_id])?;
+      for tag in tags {
+        self.conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+        self.conn.execute(
+          "INSERT OR IGNORE INTO item_tags (item_id, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+          params![item_id, tag],
+        )?;
+      }
+      Ok(())
+    })
+  }
+
+  /// SELECT - nombres de los tags de un item
+  pub fn get_item_tags(&self, item_id: i64) -> StoreResult<Vec<String>> {
+    let mut stmt = self.conn.prepare(
+      "SELECT t.name FROM tags t JOIN item_tags it ON it.tag_id = t.id WHERE it.item_id = ?1 ORDER BY t.name",
+    )?;
+    let rows = stmt.query_map(params![item_id], |r| r.get(0))?;
+
+    let mut v = Vec::new();
+    for r in rows {
+      v.push(r?);
+    }
+    Ok(v)
+  }
+
+  /// INSERT - registrar un archivo guardado en la carpeta del item
+  pub fn add_item_file(&self, item_id: i64, path: &str, role: FileRole) -> StoreResult<i64> {
+    self.conn.execute(
+      "INSERT INTO item_files (item_id, path, role) VALUES (?1, ?2, ?3)",
+      params![item_id, self.root.relative(path), role.as_str()],
+    )?;
+    Ok(self.conn.last_insert_rowid())
+  }
+
+  /// SELECT - archivos de un item
+  pub fn get_item_files(&self, item_id: i64) -> StoreResult<Vec<ItemFile>> {
+    let mut stmt = self.conn.prepare("SELECT id, item_id, path, role FROM item_files WHERE item_id = ?1 ORDER BY id")?;
+    let rows = stmt.query_map(params![item_id], |r| {
+      Ok(ItemFile {
+        id: r.get(0)?,
+        item_id: r.get(1)?,
+        path: self.root.resolve(&r.get::<_, String>(2)?),
+        role: FileRole::parse(&r.get::<_, String>(3)?),
+      })
+    })?;
+
+    let mut v = Vec::new();
+    for r in rows {
+      v.push(r?);
+    }
+    Ok(v)
+  }
+
+  /// DELETE - quitar un archivo del item
+  pub fn delete_item_file(&self, id: i64) -> StoreResult<()> {
+    self.conn.execute("DELETE FROM item_files WHERE id = ?1", params![id])?;
+    Ok(())
+  }
+
+  fn query_items(&self, sql: &str, params: &[&dyn rusqlite::ToSql]) -> StoreResult<Vec<Item>> {
+    let mut stmt = self.conn.prepare(sql)?;
+    let rows = stmt.query_map(params, |r| self.row_to_item(r))?;
+
+    let mut v = Vec::new();
+    for r in rows {
+      let mut item = r?;
+      item.tags = self.get_item_tags(item.id)?;
+      v.push(item);
+    }
+    Ok(v)
+  }
+
+  fn row_to_item(&self, r: &Row) -> rusqlite::Result<Item> {
+    Ok(Item {
+      id: r.get(0)?,
+      name: r.get(1)?,
+      category: r.get(2)?,
+      file_path: self.root.resolve(&r.get::<_, String>(3)?),
+      image_path: r.get::<_, Option<String>>(4)?.map(|p| self.root.resolve(&p)),
+      tags: Vec::new(),
+      notes: r.get(7)?,
+      created_at: r.get(5)?,
+      updated_at: r.get(6)?,
+    })
+  }
+}
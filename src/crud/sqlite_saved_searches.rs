@@ -0,0 +1,61 @@
+use crate::error::{StoreError, StoreResult};
+use crate::models::SavedSearch;
+use rusqlite::{params, Connection, Row};
+
+pub struct SavedSearchStore<'a> {
+  conn: &'a Connection,
+}
+
+impl<'a> SavedSearchStore<'a> {
+  pub fn new(conn: &'a Connection) -> Self {
+    Self { conn }
+  }
+
+  /// INSERT - guardar una búsqueda al final de la lista, devuelve el id nuevo
+  pub fn insert_search(&self, name: &str, query: &str) -> StoreResult<i64> {
+    self.conn.execute(
+      r#"
+        INSERT INTO saved_searches (name, query, sort_order)
+        SELECT ?1, ?2, ifnull(max(sort_order), 0) + 1 FROM saved_searches
+      "#,
+      params![name, query],
+    )?;
+    Ok(self.conn.last_insert_rowid())
+  }
+
+  /// SELECT - todas las búsquedas guardadas en el orden de la lista
+  pub fn get_searches(&self) -> StoreResult<Vec<SavedSearch>> {
+    let mut stmt = self.conn.prepare("SELECT id, name, query FROM saved_searches ORDER BY sort_order, name")?;
+    let rows = stmt.query_map([], Self::row_to_search)?;
+
+    let mut v = Vec::new();
+    for r in rows {
+      v.push(r?);
+    }
+    Ok(v)
+  }
+
+  /// UPDATE - reemplazar la consulta de una búsqueda guardada
+  pub fn update_query(&self, id: i64, query: &str) -> StoreResult<()> {
+    let changed = self.conn.execute("UPDATE saved_searches SET query = ?1 WHERE id = ?2", params![query, id])?;
+    if changed == 0 {
+      return Err(StoreError::NotFound(format!("búsqueda guardada {}", id)));
+    }
+    Ok(())
+  }
+
+
+  /// DELETE - eliminar una búsqueda guardada
+  pub fn delete_search(&self, id: i64) -> StoreResult<()> {
+    self.conn.execute("DELETE FROM saved_searches WHERE id = ?1", params![id])?;
+    Ok(())
+  }
+
+  fn row_to_search(r: &Row) -> rusqlite::Result<SavedSearch> {
+    Ok(SavedSearch {
+      id: r.get(0)?,
+      name: r.get(1)?,
+      query: r.get(2)?,
+    })
+  }
+}
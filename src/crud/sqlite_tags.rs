@@ -1,34 +1,27 @@
-use anyhow::Result;
+use crate::error::{StoreError, StoreResult};
 use rusqlite::{params, Connection};
+use std::collections::HashMap;
 
-pub struct TagStore {
-  conn: Connection,
+pub struct TagStore<'a> {
+  conn: &'a Connection,
 }
 
-impl TagStore {
-  pub fn new(conn: Connection) -> Self {
+impl<'a> TagStore<'a> {
+  pub fn new(conn: &'a Connection) -> Self {
     Self { conn }
   }
 
-  /// Crea la tabla `tags` si no existe
-  pub fn init(&self) -> Result<()> {
-    self.conn.execute_batch(
-      r#" CREATE TABLE IF NOT EXISTS tags (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL); "#,
-    )?;
-    Ok(())
-  }
-
-  /// INSERT - crear un nuevo tag
-  pub fn insert_tag(&self, name: &str) -> Result<()> {
+  /// INSERT - crear un nuevo tag; si ya existe devuelve `StoreError::Constraint`
+  pub fn insert_tag(&self, name: &str) -> StoreResult<()> {
     self.conn.execute(
-      "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+      "INSERT INTO tags (name) VALUES (?1)",
       params![name],
     )?;
     Ok(())
   }
 
   /// SELECT - obtener todos los tags
-  pub fn get_tags(&self) -> Result<Vec<String>> {
+  pub fn get_tags(&self) -> StoreResult<Vec<String>> {
     let mut stmt = self.conn.prepare("SELECT name FROM tags ORDER BY name ASC")?;
     let rows = stmt.query_map([], |r| r.get(0))?;
 
@@ -39,14 +32,32 @@ impl TagStore {
     Ok(v)
   }
 
-  /// UPDATE - editar un tag existente
-  pub fn update_tag(&self, old_name: &str, new_name: &str) -> Result<()> {
-    self.conn.execute("UPDATE tags SET name = ?1 WHERE name = ?2", params![new_name, old_name])?;
+  /// SELECT - cuántas carpetas de item usan cada tag
+  pub fn usage_counts(&self) -> StoreResult<HashMap<String, usize>> {
+    let mut stmt = self.conn.prepare(
+      "SELECT t.name, count(ft.file_path) FROM tags t LEFT JOIN file_tags ft ON ft.tag_id = t.id GROUP BY t.id",
+    )?;
+    let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)? as usize)))?;
+
+    let mut counts = HashMap::new();
+    for r in rows {
+      let (name, count) = r?;
+      counts.insert(name, count);
+    }
+    Ok(counts)
+  }
+
+  /// UPDATE - editar un tag existente; las carpetas e items lo referencian por id
+  pub fn update_tag(&self, old_name: &str, new_name: &str) -> StoreResult<()> {
+    let changed = self.conn.execute("UPDATE tags SET name = ?1 WHERE name = ?2", params![new_name, old_name])?;
+    if changed == 0 {
+      return Err(StoreError::NotFound(format!("tag '{}'", old_name)));
+    }
     Ok(())
   }
 
-  /// DELETE - eliminar un tag
-  pub fn delete_tag(&self, name: &str) -> Result<()> {
+  /// DELETE - eliminar un tag y quitarlo de todas las carpetas e items
+  pub fn delete_tag(&self, name: &str) -> StoreResult<()> {
     self.conn.execute("DELETE FROM tags WHERE name = ?1", params![name])?;
     Ok(())
   }
@@ -0,0 +1,303 @@
+use super::config::LibraryPaths;
+use super::crud::sqlite_categories::CategoryStore;
+use super::crud::sqlite_items::ItemStore;
+use super::crud::sqlite_saved_searches::SavedSearchStore;
+use super::crud::sqlite_tags::TagStore;
+use super::error::{StoreError, StoreResult};
+use super::library::{self, LibraryRoot, Relocation};
+use super::migrations;
+use super::scanner;
+use super::sqlite_store::SqliteStore;
+use super::trash::{self, TrashEntry};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Conexión única a `files.db`. Los stores son vistas que la toman prestada, así que todo lo que
+/// se hace dentro de [`Database::transaction`] se confirma o se deshace junto.
+///
+/// Cada hilo abre su propia `Database`; la conexión no se comparte entre hilos.
+pub struct Database {
+    conn: Connection,
+    /// Carpeta principal guardada en `library`; los stores resuelven con ella las rutas relativas
+    root: LibraryRoot,
+}
+
+impl Database {
+    pub fn open(path: &str) -> StoreResult<Self> {
+        let conn = Connection::open(path)?;
+        // La UI y el hilo de trabajo escriben cada uno con su conexión
+        conn.busy_timeout(Duration::from_secs(5))?;
+        // SQLite no comprueba las claves foráneas si no se activa en cada conexión
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        let root: Option<String> = conn.query_row("SELECT root FROM library WHERE id = 1", [], |r| r.get(0)).optional()?;
+        Ok(Self { conn, root: LibraryRoot::new(root.map(PathBuf::from)) })
+    }
+
+    /// Abre la base de la biblioteca (`.vrcfm/files.db`): crea la carpeta si hace falta, aplica las
+    /// migraciones pendientes y liga la base a la carpeta principal. Si la biblioteca se movió o se
+    /// copió desde otra carpeta, se reubica.
+    pub fn open_library(library: &LibraryPaths) -> StoreResult<OpenedLibrary> {
+        library.prepare()?;
+        let backup = migrations::run(&library.db(), library.root())?;
+        let mut db = Self::open(&library.db())?;
+
+        // Las migraciones ya ligan la base a la carpeta en la que se abrió por primera vez
+        let mut relocation = None;
+        match db.root() {
+            Some(root) if root == library.root() => {}
+            // Otra carpeta: la biblioteca se movió, y sus carpetas de item tienen que estar aquí
+            Some(_) => {
+                let moved = db.relocate(library.root())?;
+                // Sin carpetas de item no hay nada que avisar
+                relocation = Some(moved).filter(|m| m.found > 0 || !m.missing.is_empty());
+            }
+            None => return Err(StoreError::NotFound(format!("carpeta principal de {}", library.db()))),
+        }
+        Ok(OpenedLibrary { db, backup, relocation })
+    }
+
+    /// Como [`Database::open_library`], para una biblioteca que se movió a `library`: su base ya
+    /// tiene que estar en `.vrcfm/`. Una carpeta sin biblioteca es un error, no una biblioteca nueva.
+    pub fn open_moved_library(library: &LibraryPaths) -> StoreResult<OpenedLibrary> {
+        if !Path::new(&library.db()).is_file() {
+            return Err(StoreError::NotFound(format!("biblioteca en {}", library.root().display())));
+        }
+        Self::open_library(library)
+    }
+
+    /// Carpeta principal a la que está ligada la base, si ya se eligió una
+    pub fn root(&self) -> Option<&Path> {
+        self.root.path()
+    }
+
+    /// Liga la base a `root`. Las rutas guardadas relativas pasan a resolverse dentro de `root`, así
+    /// que solo tiene sentido para una base nueva o si la biblioteca se movió ahí (ver [`Database::relocate`]).
+    pub fn set_root(&mut self, root: &Path) -> StoreResult<()> {
+        self.conn.execute(
+            "INSERT INTO library (id, root) VALUES (1, ?1) ON CONFLICT(id) DO UPDATE SET root = excluded.root",
+            params![root.display().to_string()],
+        )?;
+        self.root = LibraryRoot::new(Some(root.to_path_buf()));
+        Ok(())
+    }
+
+    /// Liga la base a la biblioteca en su nueva ubicación, sin volver a importar nada. Antes
+    /// comprueba que las carpetas de item indexadas están en `new_root`; si no aparece ninguna no
+    /// se cambia nada.
+    pub fn relocate(&mut self, new_root: &Path) -> StoreResult<Relocation> {
+        if !new_root.is_dir() {
+            return Err(StoreError::NotFound(new_root.display().to_string()));
+        }
+        let moved = LibraryRoot::new(Some(new_root.to_path_buf()));
+        let mut stmt = self.conn.prepare("SELECT path FROM files")?;
+        let stored: Vec<String> = stmt.query_map([], |r| r.get(0))?.collect::<Result<_, _>>()?;
+        drop(stmt);
+
+        let relative: Vec<&String> = stored.iter().filter(|p| !library::is_absolute(p)).collect();
+        let missing: Vec<String> = relative
+            .iter()
+            .map(|p| moved.resolve(p))
+            .filter(|p| !Path::new(p).is_dir())
+            .collect();
+        if !relative.is_empty() && missing.len() == relative.len() {
+            return Err(StoreError::NotFound(format!("ninguna carpeta de la biblioteca en {}", new_root.display())));
+        }
+
+        self.set_root(new_root)?;
+        Ok(Relocation { found: relative.len() - missing.len(), missing })
+    }
+
+    /// Mueve y/o renombra la carpeta de la categoría `id` dentro de la carpeta principal y reescribe
+    /// las rutas guardadas (ver [`CategoryStore::move_category`]). Si la base falla se deshace el
+    /// movimiento en disco. Devuelve la ruta nueva de la categoría.
+    pub fn move_category(&self, id: i64, new_parent: Option<i64>, new_name: &str) -> StoreResult<String> {
+        let root = self.root().ok_or_else(|| StoreError::NotFound("carpeta principal".into()))?;
+        let categories = self.categories().get_categories()?;
+        let find = |id: i64| categories.iter().find(|c| c.id == id).ok_or_else(|| StoreError::NotFound(format!("categoría {}", id)));
+        let current = find(id)?;
+        if current.parent_id == new_parent && current.name == new_name {
+            return Ok(current.path.clone());
+        }
+        if !scanner::is_valid_folder_name(new_name) {
+            return Err(StoreError::Constraint(format!("nombre de categoría no válido: '{}'", new_name)));
+        }
+
+        let new_path = match new_parent {
+            Some(parent) => format!("{}/{}", find(parent)?.path, new_name),
+            None => new_name.to_string(),
+        };
+        let old_dir = scanner::category_dir(root, &current.path);
+        let new_dir = scanner::category_dir(root, &new_path);
+        if new_dir.starts_with(&old_dir) {
+            return Err(StoreError::Constraint("no se puede mover una categoría dentro de sí misma".into()));
+        }
+        // En sistemas de archivos sin mayúsculas (Windows, macOS) `avatars` -> `Avatars` es la misma carpeta
+        if new_dir.exists() && !same_dir(&old_dir, &new_dir) {
+            return Err(StoreError::Constraint(format!("ya existe una carpeta '{}'", new_path)));
+        }
+
+        let moved = old_dir.exists();
+        if moved {
+            rename_dir(&old_dir, &new_dir)?;
+        }
+        let result = self.categories().move_category(id, new_parent, new_name, &old_dir.display().to_string(), &new_dir.display().to_string());
+        if let Err(e) = result {
+            if moved && let Err(undo) = rename_dir(&new_dir, &old_dir) {
+                let message = format!("{}; no se pudo deshacer el movimiento de '{}': {}", e, current.path, undo);
+                return Err(StoreError::Io(io::Error::new(undo.kind(), message)));
+            }
+            return Err(e);
+        }
+        Ok(new_path)
+    }
+
+    /// Envía la carpeta `relative` a la papelera. Los tags, versiones y contenido de paquetes de sus
+    /// items se guardan con ella, porque el siguiente escaneo borra sus filas.
+    pub fn move_to_trash(&self, relative: &str) -> StoreResult<TrashEntry> {
+        let root = self.root().ok_or_else(|| StoreError::NotFound("carpeta principal".into()))?;
+        let dir = scanner::category_dir(root, relative);
+        let folders = self.files().saved_folders(&dir.display().to_string())?;
+        Ok(trash::move_to_trash(root, relative, folders)?)
+    }
+
+    /// Devuelve a su sitio una carpeta de la papelera y vuelve a indexar sus items con lo que se
+    /// guardó de ellos. Si la carpeta no se puede mover la base queda como estaba.
+    pub fn restore_from_trash(&self, entry: &TrashEntry) -> StoreResult<PathBuf> {
+        let root = self.root().ok_or_else(|| StoreError::NotFound("carpeta principal".into()))?;
+        let dir = scanner::category_dir(root, &entry.original);
+        self.transaction(|db| {
+            db.files().restore_folders(&dir.display().to_string(), &entry.folders)?;
+            Ok(trash::restore(root, entry)?)
+        })
+    }
+
+    /// Índice de carpetas de items y contenido de paquetes
+    pub fn files(&self) -> SqliteStore<'_> {
+        SqliteStore::new(&self.conn, &self.root)
+    }
+
+    pub fn tags(&self) -> TagStore<'_> {
+        TagStore::new(&self.conn)
+    }
+
+    pub fn categories(&self) -> CategoryStore<'_> {
+        CategoryStore::new(&self.conn, &self.root)
+    }
+
+    pub fn items(&self) -> ItemStore<'_> {
+        ItemStore::new(&self.conn, &self.root)
+    }
+
+    pub fn saved_searches(&self) -> SavedSearchStore<'_> {
+        SavedSearchStore::new(&self.conn)
+    }
+
+    /// Ejecuta `f` como una sola operación: si devuelve error no queda nada de lo que escribió.
+    /// Se puede anidar, y los stores que ya usan transacciones por su cuenta quedan dentro.
+    pub fn transaction<T>(&self, f: impl FnOnce(&Database) -> StoreResult<T>) -> StoreResult<T> {
+        savepoint(&self.conn, || f(self))
+    }
+}
+
+/// Si `a` y `b` son la misma carpeta en disco aunque se escriban distinto
+fn same_dir(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        matches!((fs::symlink_metadata(a), fs::symlink_metadata(b)), (Ok(a), Ok(b)) if a.dev() == b.dev() && a.ino() == b.ino())
+    }
+    #[cfg(not(unix))]
+    {
+        matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+/// Renombra la carpeta `from` a `to`. Si solo cambian mayúsculas en un sistema de archivos que no
+/// las distingue se pasa por un nombre temporal, porque no todos aceptan el cambio directo.
+fn rename_dir(from: &Path, to: &Path) -> io::Result<()> {
+    if !to.exists() || !same_dir(from, to) {
+        return fs::rename(from, to);
+    }
+    let name = from.file_name().unwrap_or_default().to_string_lossy();
+    let temp = from.with_file_name(format!(".{}.vrcfm-rename", name));
+    fs::rename(from, &temp)?;
+    fs::rename(&temp, to).inspect_err(|_| {
+        let _ = fs::rename(&temp, from);
+    })
+}
+
+/// Transacción anidable sobre `conn` mediante `SAVEPOINT`
+pub fn savepoint<T>(conn: &Connection, f: impl FnOnce() -> StoreResult<T>) -> StoreResult<T> {
+    conn.execute_batch("SAVEPOINT vrcfm")?;
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE vrcfm")?;
+            Ok(value)
+        }
+        Err(e) => {
+            // Si el rollback falla la conexión ya está rota; el error original es el que importa
+            let _ = conn.execute_batch("ROLLBACK TO vrcfm; RELEASE vrcfm");
+            Err(e)
+        }
+    }
+}
+
+/// Resultado de [`Database::open_library`]
+pub struct OpenedLibrary {
+    pub db: Database,
+    /// Copia de la base de antes de migrarla, si hubo que hacerlo
+    pub backup: Option<PathBuf>,
+    /// Si la base estaba ligada a otra carpeta y se reubicó en esta
+    pub relocation: Option<Relocation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Query;
+    use std::sync::atomic::AtomicBool;
+
+    fn scan(root: &Path, db: &Database) {
+        let categories: Vec<String> = db.categories().get_categories().unwrap().into_iter().map(|c| c.path).collect();
+        scanner::scan_library(root, &db.files(), &categories, false, &AtomicBool::new(false), &mut |_, _, _| {}).unwrap();
+    }
+
+    #[test]
+    fn trash_round_trip_keeps_tags_and_changelogs() {
+        let root = std::env::temp_dir().join(format!("vrcfm-database-{}-trash", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let item = scanner::category_dir(&root, "Avatars/Outfit");
+        fs::create_dir_all(&item).unwrap();
+        fs::write(item.join("Outfit v1.0.zip"), b"contenido").unwrap();
+        let db = Database::open_library(&LibraryPaths::new(&root)).unwrap().db;
+        let avatars = db.categories().ensure_path("Avatars").unwrap();
+        scan(&root, &db);
+
+        let item = item.display().to_string();
+        db.files().set_file_tags(&item, &["toon".to_string()]).unwrap();
+        let version = db.files().get_versions(&item).unwrap().remove(0);
+        db.files().set_version_changelog(version.id, Some("Primera versión")).unwrap();
+
+        // Borrar la categoría y volver a escanear quita sus filas de la base
+        let entry = db.move_to_trash("Avatars").unwrap();
+        db.categories().delete_category(avatars).unwrap();
+        scan(&root, &db);
+        assert!(db.files().get_files().unwrap().is_empty());
+
+        db.restore_from_trash(&entry).unwrap();
+        db.categories().ensure_path("Avatars").unwrap();
+        scan(&root, &db);
+
+        let tagged = db.files().search(&Query::parse("tag:toon").unwrap(), 0, 10).unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].file.path, item);
+        let versions = db.files().get_versions(&item).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!((versions[0].imported_at, versions[0].changelog.as_deref()), (version.imported_at, Some("Primera versión")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,101 @@
+use super::models::{DuplicateGroup, FileHash};
+use super::sqlite_store::SqliteStore;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Busca archivos con el mismo contenido en todas las carpetas de item indexadas.
+///
+/// Solo se calcula el hash de los archivos cuyo tamaño coincide con el de algún otro, y se reutiliza
+/// el de `file_hashes` si el archivo no cambió desde entonces. `on_progress` recibe los bytes
+/// leídos, el total y el archivo actual. Devuelve `None` si se canceló.
+pub fn find_duplicates(
+    store: &SqliteStore,
+    cancel: &AtomicBool,
+    on_progress: &mut dyn FnMut(u64, u64, &str),
+) -> Result<Option<Vec<DuplicateGroup>>> {
+    let mut files = Vec::new();
+    for folder in store.get_files()? {
+        files.extend(folder_files(&folder.path));
+    }
+
+    let mut known: HashMap<String, FileHash> =
+        store.get_file_hashes()?.into_iter().map(|h| (h.path.clone(), h)).collect();
+
+    let mut same_size: HashMap<u64, usize> = HashMap::new();
+    for f in &files {
+        *same_size.entry(f.size).or_default() += 1;
+    }
+
+    let mut pending = Vec::new();
+    for f in files {
+        let stored = known.remove(&f.path);
+        let unchanged = stored
+            .as_ref()
+            .is_some_and(|h| h.size == f.size && h.mtime == f.mtime && h.item_path == f.item_path);
+        if f.size > 0 && same_size[&f.size] > 1 {
+            if !unchanged {
+                pending.push(f);
+            }
+        } else if stored.is_some() && !unchanged {
+            store.delete_file_hash(&f.path)?;
+        }
+    }
+
+    // Lo que queda en `known` ya no existe
+    for path in known.keys() {
+        store.delete_file_hash(path)?;
+    }
+
+    let total: u64 = pending.iter().map(|f| f.size).sum();
+    let mut done = 0;
+    for mut f in pending {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let name = Path::new(&f.path).file_name().unwrap_or_default().to_string_lossy().to_string();
+        on_progress(done, total, &name);
+        // Un archivo que no se puede leer simplemente no entra en el informe
+        if let Ok(hash) = hash_file(Path::new(&f.path)) {
+            f.hash = hash;
+            store.set_file_hash(&f)?;
+        }
+        done += f.size;
+    }
+    on_progress(total, total, "");
+
+    Ok(Some(store.duplicate_groups()?))
+}
+
+/// Archivos de una carpeta de item (sin los ocultos), todavía sin hash
+fn folder_files(item_path: &str) -> Vec<FileHash> {
+    WalkDir::new(item_path)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+            Some(FileHash {
+                path: e.path().display().to_string(),
+                item_path: item_path.to_string(),
+                size: meta.len(),
+                mtime,
+                hash: String::new(),
+            })
+        })
+        .collect()
+}
+
+/// BLAKE3 del contenido, leyendo el archivo por bloques
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
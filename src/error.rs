@@ -0,0 +1,66 @@
+use std::fmt;
+use std::io;
+
+/// Errores de los stores de SQLite (`crud` y `SqliteStore`)
+#[derive(Debug)]
+pub enum StoreError {
+    /// Ya existe un registro con ese nombre, o se rompió otra restricción de la tabla
+    Constraint(String),
+    /// El registro pedido no existe
+    NotFound(String),
+    /// La base no se pudo llevar a la versión de esquema actual
+    Migration(String),
+    Sqlite(rusqlite::Error),
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Constraint(msg) => write!(f, "Restricción violada: {}", msg),
+            StoreError::NotFound(what) => write!(f, "No existe: {}", what),
+            StoreError::Migration(msg) => write!(f, "Error migrando la base de datos: {}", msg),
+            StoreError::Sqlite(e) => write!(f, "Error de base de datos: {}", e),
+            StoreError::Io(e) => write!(f, "Error de archivo: {}", e),
+            StoreError::Json(e) => write!(f, "Error de JSON: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Sqlite(e) => Some(e),
+            StoreError::Io(e) => Some(e),
+            StoreError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(err, msg) if err.code == rusqlite::ErrorCode::ConstraintViolation => {
+                StoreError::Constraint(msg.unwrap_or_else(|| err.to_string()))
+            }
+            rusqlite::Error::QueryReturnedNoRows => StoreError::NotFound("registro".into()),
+            e => StoreError::Sqlite(e),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Json(e)
+    }
+}
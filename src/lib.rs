@@ -0,0 +1,24 @@
+//! Biblioteca de VRC Files Manager: índice de carpetas, búsqueda, importación y tareas en segundo
+//! plano. La usan la aplicación de escritorio y la línea de comandos `vrcfm`.
+
+pub mod config;
+pub mod models;
+pub mod sqlite_store;
+pub mod database;
+pub mod error;
+pub mod migrations;
+pub mod query;
+pub mod scanner;
+pub mod dedup;
+pub mod versions;
+pub mod library;
+pub mod unitypackage;
+pub mod trash;
+pub mod worker;
+
+pub mod crud {
+    pub mod sqlite_tags;
+    pub mod sqlite_categories;
+    pub mod sqlite_items;
+    pub mod sqlite_saved_searches;
+}
@@ -0,0 +1,57 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Carpeta principal de la biblioteca a la que está ligada la base. Las rutas que quedan dentro se
+/// guardan relativas a ella y con `/` (`Avatars/Manuka/Outfit`), así la biblioteca se puede mover
+/// de sitio; las de fuera se guardan tal cual.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryRoot(Option<PathBuf>);
+
+impl LibraryRoot {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self(root)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.0.as_deref()
+    }
+
+    /// Ruta tal como se guarda en la base
+    pub fn relative(&self, path: &str) -> String {
+        let Some(root) = &self.0 else { return path.to_string() };
+        match Path::new(path).strip_prefix(root) {
+            Ok(relative) => relative
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name.to_string_lossy()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => path.to_string(),
+        }
+    }
+
+    /// Ruta completa de una ruta guardada en la base
+    pub fn resolve(&self, stored: &str) -> String {
+        match &self.0 {
+            Some(root) if !is_absolute(stored) => {
+                stored.split('/').fold(root.clone(), |dir, name| dir.join(name)).display().to_string()
+            }
+            _ => stored.to_string(),
+        }
+    }
+}
+
+/// Si una ruta guardada es absoluta (de fuera de la biblioteca o de antes de guardarlas relativas)
+pub fn is_absolute(stored: &str) -> bool {
+    Path::new(stored).has_root() || Path::new(stored).is_absolute()
+}
+
+/// Resultado de reubicar la biblioteca
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Relocation {
+    /// Carpetas de item indexadas que se encontraron en la nueva ubicación
+    pub found: usize,
+    /// Las que no están; desaparecen del índice con el próximo escaneo
+    pub missing: Vec<String>,
+}
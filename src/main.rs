@@ -1,113 +1,212 @@
 use eframe::egui;
 use rfd::FileDialog;
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-
-mod models;
-use models::IndexedFile;
-
-mod sqlite_store;
-use sqlite_store::SqliteStore as Store;
-
-mod crud {
-    pub mod sqlite_tags;
-    pub mod sqlite_categories;
-}
-
-use crate::crud::sqlite_tags::TagStore;
-use crate::crud::sqlite_categories::CategoryStore;
-
-#[derive(Serialize, Deserialize, Default, Clone)]
-struct AppConfig {
-    root_path: String,
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use vrcfm::config::{self, AppConfig, LibraryPaths, LibrarySettings};
+use vrcfm::database::{Database, OpenedLibrary};
+use vrcfm::error::StoreError;
+use vrcfm::library::Relocation;
+use vrcfm::models::{Category, DuplicateGroup, FileLink, Item, ItemVersion, PackageAsset, SavedSearch, SearchHit};
+use vrcfm::query::{ParseError, Query};
+use vrcfm::scanner::{self, ScanSummary};
+use vrcfm::sqlite_store::{MATCH_END, MATCH_START};
+use vrcfm::trash::{self, TrashEntry};
+use vrcfm::unitypackage;
+use vrcfm::worker::{CollisionPolicy, Task, Worker, WorkerEvent};
+
+mod searcher;
+use searcher::{SavedCounts, Searcher};
+
+mod thumbnails;
+use thumbnails::ThumbnailCache;
+
+mod notifications;
+use notifications::Notifications;
+
+/// Lo que se va a mandar a la papelera al eliminar una categoría
+struct DeletePreview {
+    category: String,
+    items: Vec<String>,
+    size: u64,
 }
 
 struct MyApp {
-    store: Store,
+    db: Database,
     search_query: String,
-    results: Vec<IndexedFile>,
+    /// Error de sintaxis de la última búsqueda; se muestra bajo el buscador
+    search_error: Option<ParseError>,
+    searcher: Searcher,
+    /// Cuándo se editó el buscador por última vez, si aún no se buscó lo escrito
+    search_edited: Option<Instant>,
+    /// Esperando una página del hilo de búsqueda
+    search_loading: bool,
+    /// La última página llegó llena: puede haber más resultados
+    search_more: bool,
+    results: Vec<SearchHit>,
+    saved_searches: Vec<SavedSearch>,
+    /// Resultados de cada búsqueda guardada; `None` si hay que volver a contarlos
+    saved_counts: Option<SavedCounts>,
+    /// Búsqueda guardada que está en el buscador, mientras no se edite
+    active_saved_search: Option<i64>,
+    /// Nombre de la búsqueda que se está guardando
+    new_saved_search: Option<String>,
+    asset_results: Vec<PackageAsset>,
+    gallery_mode: bool,
+    thumbnails: ThumbnailCache,
+    notifications: Notifications,
+    show_log: bool,
+
+    trash_retention_days: u32,
+    pending_delete: Option<DeletePreview>,
+    show_trash: bool,
+
+    show_duplicates: bool,
+    duplicates: Vec<DuplicateGroup>,
+    /// Copia que se conserva de cada grupo (por hash), como índice en `paths`
+    duplicate_keep: HashMap<String, usize>,
+    file_links: Vec<FileLink>,
+    /// Carpeta de item y sus versiones, en la ventana de historial
+    item_versions: Option<(String, Vec<ItemVersion>)>,
+    /// (id de la versión, texto) de las notas que se están editando
+    edit_changelog: Option<(i64, String)>,
+    trash_entries: Vec<TrashEntry>,
     root_path: String,
+    library: LibraryPaths,
+    last_scan: Option<ScanSummary>,
+    hash_on_scan: bool,
+    worker: Option<Worker>,
+    progress: Option<(String, u64, u64)>,
 
     show_tag_manager: bool,
     new_tag: String,
     edit_tag: Option<(String, String)>,
+    /// Carpetas que usan cada tag; `None` si hay que volver a contarlas
+    tag_usage: Option<HashMap<String, usize>>,
+    /// Tag en uso pendiente de confirmar su borrado
+    pending_tag_delete: Option<String>,
     tags: Vec<String>,
-    tag_store: TagStore,
 
     show_category_manager: bool,
     new_category: String,
-    edit_category: Option<(String, String)>,
-    categories: Vec<String>,
-    categories_store: CategoryStore,
+    /// (id, nombre nuevo) de la categoría que se está renombrando
+    edit_category: Option<(i64, String)>,
+    /// Árbol de categorías en orden de visualización
+    categories: Vec<Category>,
+    /// (id del padre, nombre) de la subcategoría que se está creando
+    new_subcategory: Option<(i64, String)>,
 
 		show_item_manager: bool,
     new_item_name: String,
+    new_item_notes: String,
+    /// Notas de la versión que se importa
+    new_item_changelog: String,
+    /// Ruta de la categoría elegida para el item nuevo
     selected_category: Option<String>,
     selected_tags: Vec<String>,
-    item_file_path: Option<String>,
+    item_file_paths: Vec<String>,
     item_image_path: Option<String>,
+    item_is_package: bool,
+    use_package_preview: bool,
+    collision_policy: CollisionPolicy,
 }
 
-const CONFIG_FILE: &str = "config.json";
+/// Pausa al escribir antes de lanzar la búsqueda
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(250);
 
-impl MyApp {
-    fn new(mut store: Store, tag_store: TagStore, categories_store: CategoryStore) -> Self {
-        let config = Self::load_or_create_config();
-
-        // Insertar archivo de ejemplo
-        let example_path = format!("{}/ejemplo.txt", config.root_path);
-        let _ = store.insert_file(&IndexedFile {
-            path: example_path.clone(),
-            name: "ejemplo.txt".into(),
-            tags: vec!["demo".into()],
-        });
+const SEARCH_HELP: &str = "Palabras sueltas (por prefijo) o \"frase exacta\"\n\
+tag:nombre   category:Avatars/Manuka   ext:unitypackage\n\
+size:>50MB   added:<30d   added:>=2024-01-31\n\
+Un - delante excluye el término";
 
-        let tags = tag_store.get_tags().unwrap_or_default();
-        let categories = categories_store.get_categories().unwrap_or_default();
+/// Tamaño de las tarjetas de la galería
+const CARD_WIDTH: f32 = 180.0;
+const CARD_HEIGHT: f32 = 260.0;
+
+impl MyApp {
+    fn new(
+        ctx: &egui::Context,
+        library: LibraryPaths,
+        opened: OpenedLibrary,
+    ) -> Self {
+        let settings = LibrarySettings::load(&library);
 
         let mut app = Self {
-            store,
-            tag_store,
-            categories_store,
+            db: opened.db,
+            new_subcategory: None,
             search_query: String::new(),
+            search_error: None,
+            searcher: Searcher::spawn(&library.db(), ctx),
+            search_edited: None,
+            search_loading: false,
+            search_more: false,
+            saved_searches: Vec::new(),
+            saved_counts: None,
+            active_saved_search: None,
+            new_saved_search: None,
             results: Vec::new(),
-            root_path: config.root_path.clone(),
+            asset_results: Vec::new(),
+            gallery_mode: false,
+            thumbnails: ThumbnailCache::new(&library.thumbnails(), ctx),
+            notifications: Notifications::new(config::app_dir().join(config::LOG_FILE)),
+            show_log: false,
+            trash_retention_days: settings.trash_retention_days,
+            pending_delete: None,
+            show_trash: false,
+            show_duplicates: false,
+            duplicates: Vec::new(),
+            duplicate_keep: HashMap::new(),
+            file_links: Vec::new(),
+            item_versions: None,
+            edit_changelog: None,
+            trash_entries: Vec::new(),
+            root_path: library.root().display().to_string(),
+            library,
+            last_scan: None,
+            hash_on_scan: false,
+            worker: None,
+            progress: None,
 
 						// TAGS
             show_tag_manager: false,
             new_tag: String::new(),
             edit_tag: None,
-            tags,
+            tag_usage: None,
+            pending_tag_delete: None,
+            tags: Vec::new(),
 
 						// CATEGORIAS
             show_category_manager: false,
             new_category: String::new(),
             edit_category: None,
-            categories,
-						item_file_path: None,
+            categories: Vec::new(),
+						item_file_paths: Vec::new(),
 						item_image_path: None,
+						item_is_package: false,
+						use_package_preview: true,
+						collision_policy: CollisionPolicy::default(),
 						new_item_name: String::new(),
+						new_item_notes: String::new(),
+						new_item_changelog: String::new(),
 						selected_category: None,
 						selected_tags: Vec::new(),
-						show_item_manager: false
+						show_item_manager: false,
         };
 
-        // Sincronizar categorías con la carpeta principal
-        app.sync_categories_with_fs();
+        app.load_library(ctx);
+        app.report_opened(opened.backup, opened.relocation);
 
         app
     }
 
+    /// La biblioteca configurada; si no hay ninguna o su carpeta ya no existe se pide otra
     fn load_or_create_config() -> AppConfig {
-        if Path::new(CONFIG_FILE).exists() {
-            if let Ok(contents) = fs::read_to_string(CONFIG_FILE) {
-                if let Ok(config) = serde_json::from_str::<AppConfig>(&contents) {
-                    if !config.root_path.is_empty() {
-                        return config;
-                    }
-                }
-            }
+        if let Some(config) = AppConfig::load()
+            && Path::new(&config.root_path).is_dir()
+        {
+            return config;
         }
 
         let path = FileDialog::new()
@@ -116,60 +215,547 @@ impl MyApp {
             .map(|p| p.display().to_string())
             .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| Path::new(".").to_path_buf()).display().to_string());
 
-        let config = AppConfig { root_path: path };
-        let _ = fs::write(CONFIG_FILE, serde_json::to_string_pretty(&config).unwrap());
-        config
+        AppConfig { root_path: path }
     }
 
-    fn save_config(&self) {
-        let config = AppConfig {
-            root_path: self.root_path.clone(),
+    fn save_config(&mut self) {
+        let config = AppConfig { root_path: self.root_path.clone() };
+        if let Err(e) = config.save() {
+            self.notifications.error_at(format!("No se pudo guardar la configuración: {}", e), AppConfig::path());
+        }
+        let settings = LibrarySettings { trash_retention_days: self.trash_retention_days };
+        if let Err(e) = settings.save(&self.library) {
+            self.notifications.error_at(format!("No se pudieron guardar los ajustes de la biblioteca: {}", e), self.library.settings());
+        }
+    }
+
+    /// Cambia a la biblioteca de `root`, con la base, las miniaturas y los ajustes de su `.vrcfm/`
+    fn open_library(&mut self, root: PathBuf, ctx: &egui::Context) {
+        if self.worker.is_some() {
+            self.notifications.warn("Espera a que termine la tarea en curso para cambiar de biblioteca");
+            return;
+        }
+        let library = LibraryPaths::new(root);
+        let Some(opened) = self.notifications.check("Error abriendo la biblioteca", Database::open_library(&library)) else {
+            return;
         };
-        let _ = fs::write(CONFIG_FILE, serde_json::to_string_pretty(&config).unwrap());
+
+        self.db = opened.db;
+        self.root_path = library.root().display().to_string();
+        self.trash_retention_days = LibrarySettings::load(&library).trash_retention_days;
+        self.searcher = Searcher::spawn(&library.db(), ctx);
+        self.thumbnails = ThumbnailCache::new(&library.thumbnails(), ctx);
+        self.library = library;
+        self.results.clear();
+        self.asset_results.clear();
+        self.active_saved_search = None;
+        self.selected_category = None;
+        self.item_versions = None;
+        self.duplicates.clear();
+        self.tag_usage = None;
+        self.last_scan = None;
+
+        self.load_library(ctx);
+        self.report_opened(opened.backup, opened.relocation);
+    }
+
+    /// Lee tags, categorías y búsquedas de la biblioteca recién abierta, la sincroniza con sus
+    /// carpetas y la vuelve a indexar
+    fn load_library(&mut self, ctx: &egui::Context) {
+        self.save_config();
+        self.tags = self.notifications.check("Error leyendo tags", self.db.tags().get_tags()).unwrap_or_default();
+        self.refresh_categories();
+        self.refresh_saved_searches();
+        self.purge_trash();
+
+        // Sincronizar categorías con la carpeta principal
+        self.sync_categories_with_fs();
+        self.scan_library(ctx);
+        self.run_search();
     }
 
-    /// Sincroniza categorías en DB con carpetas físicas
+    /// Avisa si la base se migró o si la biblioteca venía de otra carpeta
+    fn report_opened(&mut self, backup: Option<PathBuf>, relocation: Option<Relocation>) {
+        if let Some(backup) = backup {
+            self.notifications.info(format!("Base de datos actualizada; copia anterior en {}", backup.display()));
+        }
+        if let Some(relocation) = relocation {
+            self.notifications.info(format!(
+                "Biblioteca reubicada en {} ({} carpetas encontradas)",
+                self.root_path, relocation.found
+            ));
+            if !relocation.missing.is_empty() {
+                self.notifications.warn(format!(
+                    "{} carpetas no están en la nueva ubicación: {}",
+                    relocation.missing.len(),
+                    relocation.missing.join(", ")
+                ));
+            }
+        }
+    }
+
+    /// Sincroniza el árbol de categorías en DB con las carpetas físicas, a cualquier profundidad
     fn sync_categories_with_fs(&mut self) {
         if !Path::new(&self.root_path).exists() {
+            self.notifications.warn(format!("La carpeta principal no existe: {}", self.root_path));
             return;
         }
 
-        let mut existing_folders = Vec::new();
-        if let Ok(entries) = fs::read_dir(&self.root_path) {
-            for entry in entries.flatten() {
-                if entry.path().is_dir() {
-                    if let Some(name) = entry.file_name().to_str() {
-                        existing_folders.push(name.to_string());
+        if let Err(e) = fs::read_dir(&self.root_path) {
+            self.notifications.error_at(format!("No se pudo leer la carpeta principal: {}", e), &self.root_path);
+            return;
+        }
+
+        let Some(db_categories) = self.notifications.check("Error leyendo categorías", self.db.categories().get_categories()) else {
+            return;
+        };
+        let known: Vec<String> = db_categories.iter().map(|c| c.path.clone()).collect();
+        let on_disk = scanner::category_folders(Path::new(&self.root_path), &known);
+
+        // Registrar carpetas nuevas y quitar las categorías que ya no existan como carpetas
+        let result = self.db.categories().sync_paths(&on_disk);
+        self.notifications.check("Error sincronizando categorías con la carpeta principal", result);
+
+        self.refresh_categories();
+    }
+
+    fn refresh_categories(&mut self) {
+        self.categories = self.notifications.check("Error leyendo categorías", self.db.categories().get_categories()).unwrap_or_default();
+        self.saved_counts = None;
+    }
+
+    fn refresh_saved_searches(&mut self) {
+        let searches = self.db.saved_searches().get_searches();
+        self.saved_searches = self.notifications.check("Error leyendo búsquedas guardadas", searches).unwrap_or_default();
+        self.saved_counts = None;
+    }
+
+    /// Pone `query` en el buscador y la ejecuta
+    fn search_for(&mut self, query: String, saved: Option<i64>) {
+        self.search_query = query;
+        self.active_saved_search = saved;
+        self.run_search();
+    }
+
+    /// Barra lateral con las categorías y las búsquedas guardadas; ambas rellenan el buscador
+    fn show_sidebar(&mut self, ui: &mut egui::Ui) {
+        let mut search = None;
+        let mut update_saved = None;
+        let mut delete_saved = None;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.heading("Categorías");
+            for cat in &self.categories {
+                ui.horizontal(|ui| {
+                    ui.add_space(cat.depth() as f32 * 12.0);
+                    let query = format!("category:\"{}\"", cat.path);
+                    if ui.selectable_label(self.search_query == query, &cat.name).clicked() {
+                        search = Some((query, None));
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.heading("Búsquedas guardadas");
+            let counts = self.saved_counts.get_or_insert_with(HashMap::new);
+            for saved in &self.saved_searches {
+                let count = match counts.get(&saved.id) {
+                    Some(Some(n)) => n.to_string(),
+                    Some(None) => "?".into(),
+                    None => "…".into(),
+                };
+                let label = format!("{} ({})", saved.name, count);
+                let response = ui
+                    .selectable_label(self.active_saved_search == Some(saved.id), label)
+                    .on_hover_text(&saved.query);
+                if response.clicked() {
+                    search = Some((saved.query.clone(), Some(saved.id)));
+                }
+                response.context_menu(|ui| {
+                    if ui.button("Reemplazar con la búsqueda actual").clicked() {
+                        update_saved = Some(saved.id);
+                        ui.close();
+                    }
+                    if ui.button("Eliminar").clicked() {
+                        delete_saved = Some(saved.id);
+                        ui.close();
                     }
+                });
+            }
+
+            let mut create = false;
+            let mut cancel = false;
+            match &mut self.new_saved_search {
+                Some(name) => {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(name);
+                        create = ui.button("Guardar").clicked();
+                        cancel = ui.button("Cancelar").clicked();
+                    });
                 }
+                None => {
+                    let can_save = !self.search_query.trim().is_empty() && self.search_error.is_none();
+                    if ui.add_enabled(can_save, egui::Button::new("⭐ Guardar búsqueda actual")).clicked() {
+                        self.new_saved_search = Some(String::new());
+                    }
+                }
+            }
+            if create && let Some(name) = self.new_saved_search.as_ref().map(|n| n.trim().to_string()) && !name.is_empty() {
+                let result = self.db.saved_searches().insert_search(&name, self.search_query.trim());
+                if let Some(id) = self.notifications.check("Error guardando la búsqueda", result) {
+                    self.active_saved_search = Some(id);
+                    self.new_saved_search = None;
+                }
+                self.refresh_saved_searches();
             }
+            if cancel {
+                self.new_saved_search = None;
+            }
+        });
+
+        if let Some(id) = update_saved {
+            let result = self.db.saved_searches().update_query(id, self.search_query.trim());
+            self.notifications.check("Error guardando la búsqueda", result);
+            self.active_saved_search = Some(id);
+            self.refresh_saved_searches();
+        }
+        if let Some(id) = delete_saved {
+            let result = self.db.saved_searches().delete_search(id);
+            self.notifications.check("Error eliminando la búsqueda", result);
+            if self.active_saved_search == Some(id) {
+                self.active_saved_search = None;
+            }
+            self.refresh_saved_searches();
         }
+        if let Some((query, saved)) = search {
+            self.search_for(query, saved);
+        }
+    }
 
-        let mut db_categories = self.categories_store.get_categories().unwrap_or_default();
+    /// Crea la carpeta de una categoría nueva dentro de `parent` (o en la raíz) y la registra
+    fn create_category(&mut self, parent: Option<i64>, name: &str) -> bool {
+        if !scanner::is_valid_folder_name(name) {
+            self.notifications.warn(format!("Nombre de categoría no válido: '{}'", name));
+            return false;
+        }
+        let parent_path = parent.and_then(|id| self.category(id)).map(|c| c.path.clone());
+        let path = match &parent_path {
+            Some(p) => format!("{}/{}", p, name),
+            None => name.to_string(),
+        };
+
+        let dir = scanner::category_dir(Path::new(&self.root_path), &path);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            self.notifications.error_at(format!("Error creando carpeta de categoría: {}", e), &self.root_path);
+            return false;
+        }
+
+        let result = self.db.categories().insert_category(parent, name);
+        let created = self.notifications.check("Error creando categoría", result).is_some();
+        self.refresh_categories();
+        created
+    }
 
-        // Insertar carpetas nuevas en DB
-        for folder in &existing_folders {
-            if !db_categories.contains(folder) {
-                let _ = self.categories_store.insert_category(folder);
-                db_categories.push(folder.clone());
+    fn category(&self, id: i64) -> Option<&Category> {
+        self.categories.iter().find(|c| c.id == id)
+    }
+
+    /// Mueve y/o renombra la carpeta de la categoría y actualiza la DB (ver
+    /// [`Database::move_category`]). Devuelve si se completó.
+    fn move_category(&mut self, ctx: &egui::Context, id: i64, new_parent: Option<i64>, new_name: &str) -> bool {
+        let Some(current) = self.category(id).cloned() else { return false };
+        if current.parent_id == new_parent && current.name == new_name {
+            return true;
+        }
+        if self.worker.is_some() {
+            self.notifications.warn("Espera a que termine la tarea en curso para mover o renombrar");
+            return false;
+        }
+
+        let ok = match self.db.move_category(id, new_parent, new_name) {
+            Ok(new_path) => {
+                self.notifications.info(format!("Categoría '{}' movida a '{}'", current.path, new_path));
+                true
             }
+            Err(e) => {
+                self.notifications.error(format!("Error moviendo categoría: {}", e));
+                false
+            }
+        };
+
+        self.refresh_categories();
+        if ok {
+            self.thumbnails.clear_failed();
+            self.scan_library(ctx);
         }
+        ok
+    }
+
+    /// Sube (`-1`) o baja (`1`) la categoría entre sus hermanas
+    fn reorder_category(&mut self, id: i64, delta: isize) {
+        let Some(current) = self.category(id) else { return };
+        let siblings: Vec<i64> = self.categories.iter().filter(|c| c.parent_id == current.parent_id).map(|c| c.id).collect();
+        let Some(pos) = siblings.iter().position(|s| *s == id) else { return };
+        let Some(other) = pos.checked_add_signed(delta).and_then(|p| siblings.get(p)) else { return };
 
-        // Eliminar de DB categorías que ya no existan como carpetas
-        for cat in db_categories.clone() {
-            if !existing_folders.contains(&cat) {
-                let _ = self.categories_store.delete_category(&cat);
-                db_categories.retain(|x| x != &cat);
+        let result = self.db.categories().swap_sort_order(id, *other);
+        self.notifications.check("Error ordenando categorías", result);
+        self.refresh_categories();
+    }
+
+    /// Prepara la confirmación para eliminar una categoría
+    fn preview_delete(&self, category: &str) -> DeletePreview {
+        let path = scanner::category_dir(Path::new(&self.root_path), category);
+        let mut items: Vec<String> = fs::read_dir(&path)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|e| e.path().is_dir())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        items.sort();
+
+        DeletePreview {
+            category: category.to_string(),
+            items,
+            size: scanner::folder_metadata(&path).size,
+        }
+    }
+
+    /// Mueve la carpeta de la categoría (y sus subcategorías) a la papelera y la quita de la DB
+    fn delete_category(&mut self, ctx: &egui::Context, category: &str) {
+        let root = PathBuf::from(&self.root_path);
+        match self.db.move_to_trash(category) {
+            Ok(_) => {
+                self.notifications.info(format!("'{}' se movió a la papelera", category));
+            }
+            Err(StoreError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                self.notifications.error_at(format!("Error moviendo '{}' a la papelera: {}", category, e), scanner::category_dir(&root, category));
+                return;
+            }
+        }
+
+        if let Some(cat) = self.categories.iter().find(|c| c.path == category) {
+            let result = self.db.categories().delete_category(cat.id);
+            self.notifications.check("Error eliminando categoría", result);
+        }
+        self.refresh_categories();
+        self.refresh_trash();
+        self.scan_library(ctx);
+    }
+
+    fn restore_from_trash(&mut self, ctx: &egui::Context, entry: &TrashEntry) {
+        match self.db.restore_from_trash(entry) {
+            Ok(_) => {
+                self.notifications.info(format!("'{}' restaurada", entry.original));
+                self.sync_categories_with_fs();
+                self.scan_library(ctx);
             }
+            Err(e) => self.notifications.error(format!("Error restaurando '{}': {}", entry.original, e)),
+        }
+        self.refresh_trash();
+    }
+
+    fn purge_trash_entry(&mut self, entry: &TrashEntry) {
+        if let Err(e) = trash::purge(Path::new(&self.root_path), entry) {
+            self.notifications.error_at(format!("Error vaciando '{}': {}", entry.original, e), entry.path(Path::new(&self.root_path)));
         }
+        self.refresh_trash();
+    }
 
-        self.categories = db_categories;
+    /// Borra definitivamente lo que lleva en la papelera más días de los configurados
+    fn purge_trash(&mut self) {
+        match trash::purge_older_than(Path::new(&self.root_path), self.trash_retention_days) {
+            Ok(0) => {}
+            Ok(n) => self.notifications.info(format!("Se vaciaron {} elementos antiguos de la papelera", n)),
+            Err(e) => self.notifications.error(format!("Error vaciando la papelera: {}", e)),
+        }
+        self.refresh_trash();
+    }
+
+    /// Calcula en segundo plano los hashes de los archivos de la biblioteca y abre el informe
+    fn find_duplicates(&mut self, ctx: &egui::Context) {
+        if self.worker.is_some() {
+            return;
+        }
+        self.duplicates.clear();
+        let links = self.db.files().get_file_links();
+        self.file_links = self.notifications.check("Error leyendo copias reemplazadas", links).unwrap_or_default();
+        self.worker = Some(Worker::spawn(&self.library.db(), Task::FindDuplicates, repaint(ctx)));
+        self.show_duplicates = true;
+    }
+
+    /// Deja solo la copia `keep` del grupo: las demás van a la papelera y la base apunta a `keep`
+    fn keep_duplicate(&mut self, group: &DuplicateGroup, keep: usize) {
+        if self.worker.is_some() {
+            self.notifications.warn("Espera a que termine la tarea en curso para eliminar duplicados");
+            return;
+        }
+        let root = PathBuf::from(&self.root_path);
+        let target = &group.paths[keep];
+        let mut removed = 0;
+        for path in group.paths.iter().filter(|p| *p != target) {
+            let Ok(relative) = Path::new(path).strip_prefix(&root) else {
+                self.notifications.warn(format!("{} está fuera de la carpeta principal", path));
+                continue;
+            };
+            // La base se actualiza primero; si el archivo no se puede mover se deshace
+            let result = self.db.transaction(|db| {
+                db.files().link_duplicate(path, target)?;
+                trash::move_to_trash(&root, &relative.to_string_lossy(), Vec::new())?;
+                Ok(())
+            });
+            if self.notifications.check(&format!("Error enviando {} a la papelera", path), result).is_some() {
+                removed += 1;
+            }
+        }
+
+        self.notifications.info(format!("{} copias enviadas a la papelera", removed));
+        self.duplicates.retain(|g| g.hash != group.hash);
+        let links = self.db.files().get_file_links();
+        self.file_links = self.notifications.check("Error leyendo copias reemplazadas", links).unwrap_or_default();
+        self.refresh_trash();
+    }
+
+    /// Abre el historial de versiones de una carpeta de item
+    fn show_versions(&mut self, item_path: &str) {
+        let versions = self.db.files().get_versions(item_path);
+        if let Some(versions) = self.notifications.check("Error leyendo versiones", versions) {
+            self.item_versions = Some((item_path.to_string(), versions));
+            self.edit_changelog = None;
+        }
+    }
+
+    /// Interpreta `search_query` y lanza la búsqueda en segundo plano; si no se entiende, deja el
+    /// error para mostrarlo y conserva los resultados anteriores
+    fn run_search(&mut self) {
+        self.search_edited = None;
+        let query = match Query::parse(&self.search_query) {
+            Ok(query) => query,
+            Err(e) => {
+                self.search_error = Some(e);
+                return;
+            }
+        };
+        self.search_error = None;
+        self.searcher.search(query);
+        self.search_loading = true;
+    }
+
+    /// Busca lo escrito cuando se deja de teclear y recoge las páginas que lleguen
+    fn poll_search(&mut self, ctx: &egui::Context) {
+        if let Some(edited) = self.search_edited {
+            let elapsed = edited.elapsed();
+            if elapsed >= SEARCH_DEBOUNCE {
+                self.run_search();
+            } else {
+                ctx.request_repaint_after(SEARCH_DEBOUNCE - elapsed);
+            }
+        }
+
+        for page in self.searcher.poll() {
+            self.search_loading = false;
+            self.search_more = page.has_more();
+            if page.offset == 0 {
+                self.results.clear();
+                // Qué paquetes contienen assets con esa ruta
+                self.asset_results = self.notifications.check("Error buscando en paquetes", page.assets).unwrap_or_default();
+            }
+            if let Some(hits) = self.notifications.check("Error al buscar", page.hits) {
+                self.results.extend(hits);
+            }
+        }
+        if let Some(counts) = self.searcher.poll_counts() {
+            self.saved_counts = Some(counts);
+        }
+    }
+
+    /// Pide la siguiente página si se llegó al final de los resultados mostrados
+    fn load_more_results(&mut self, reached_end: bool) {
+        if reached_end && self.search_more && !self.search_loading {
+            self.searcher.load_more(self.results.len());
+            self.search_loading = true;
+        }
+    }
+
+    fn refresh_trash(&mut self) {
+        let entries = trash::list(Path::new(&self.root_path));
+        self.trash_entries = self.notifications.check("Error leyendo la papelera", entries).unwrap_or_default();
+    }
+
+    /// Indexa las carpetas de items de la carpeta principal en la tabla `files` en segundo plano
+    fn scan_library(&mut self, ctx: &egui::Context) {
+        if !Path::new(&self.root_path).exists() || self.worker.is_some() {
+            return;
+        }
+
+        let task = Task::Scan {
+            root: PathBuf::from(&self.root_path),
+            hash_contents: self.hash_on_scan,
+        };
+        self.worker = Some(Worker::spawn(&self.library.db(), task, repaint(ctx)));
+    }
+
+    /// Procesa los mensajes del hilo de trabajo
+    fn poll_worker(&mut self) {
+        let Some(worker) = &self.worker else { return };
+
+        for event in worker.poll() {
+            match event {
+                WorkerEvent::Progress { label, done, total } => {
+                    self.progress = Some((label, done, total));
+                    continue;
+                }
+                WorkerEvent::Warning(message) => {
+                    self.notifications.warn(message);
+                    continue;
+                }
+                WorkerEvent::ScanFinished(mut summary) => {
+                    for warning in std::mem::take(&mut summary.warnings) {
+                        self.notifications.warn(warning);
+                    }
+                    self.last_scan = Some(summary);
+                    self.thumbnails.clear_failed();
+                    self.tag_usage = None;
+                    self.saved_counts = None;
+                    self.run_search();
+                }
+                WorkerEvent::ItemImported => {
+                    self.notifications.info("Item guardado");
+                    self.thumbnails.clear_failed();
+                    self.tag_usage = None;
+                    self.saved_counts = None;
+                    // Refrescar la búsqueda para que aparezca el item nuevo
+                    self.run_search();
+                }
+                WorkerEvent::DuplicatesFound(groups) => {
+                    self.notifications.info(format!("{} grupos de archivos duplicados", groups.len()));
+                    self.duplicates = groups;
+                    self.duplicate_keep.clear();
+                }
+                WorkerEvent::Cancelled => self.notifications.info("Operación cancelada"),
+                WorkerEvent::Failed(e) => self.notifications.error(format!("Error en segundo plano: {}", e)),
+            }
+            // Cualquier otro mensaje es el último del hilo
+            self.worker = None;
+            self.progress = None;
+            break;
+        }
     }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let mut path_changed = false;
+        let mut open_root = None;
+        self.poll_worker();
+        self.poll_search(ctx);
+        self.thumbnails.update(ctx);
+        for e in self.thumbnails.take_errors() {
+            self.notifications.warn(e);
+        }
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -182,23 +768,67 @@ impl eframe::App for MyApp {
 								if ui.button("Manage Items").clicked() {
 										self.show_item_manager = true;
 								}
+                if ui.add_enabled(self.worker.is_none(), egui::Button::new("Scan Library")).clicked() {
+                    self.scan_library(ctx);
+                }
+                ui.checkbox(&mut self.hash_on_scan, "Hash");
+
+                if ui.add_enabled(self.worker.is_none(), egui::Button::new("🔁 Duplicados")).clicked() {
+                    self.find_duplicates(ctx);
+                }
+
+                if ui.button("🗑 Papelera").clicked() {
+                    self.refresh_trash();
+                    self.show_trash = true;
+                }
+
+                let errors = self.notifications.error_count();
+                let log_label = if errors > 0 { format!("📋 Log ({} ❌)", errors) } else { "📋 Log".to_string() };
+                ui.toggle_value(&mut self.show_log, log_label);
+
+                if let Some(worker) = &self.worker {
+                    let (label, done, total) = self.progress.clone().unwrap_or_default();
+                    let fraction = if total > 0 { done as f32 / total as f32 } else { 0.0 };
+                    ui.add(egui::ProgressBar::new(fraction).desired_width(200.0).text(label));
+                    if ui.add_enabled(!worker.is_cancelling(), egui::Button::new("❌ Cancel")).clicked() {
+                        worker.cancel();
+                    }
+                } else if let Some(summary) = &self.last_scan {
+                    ui.label(format!(
+                        "Nuevos: {} | Modificados: {} | Faltantes: {} | Sin cambios: {}",
+                        summary.new, summary.modified, summary.missing, summary.unchanged
+                    ));
+                }
             });
         });
 
+        if self.show_log {
+            self.notifications.show_log(ctx);
+        }
+
+        // Las búsquedas guardadas se recuentan en el hilo del buscador cuando cambia la biblioteca;
+        // mientras tanto se muestran como pendientes
+        if self.saved_counts.is_none() {
+            self.searcher.count(&self.saved_searches);
+            self.saved_counts = Some(HashMap::new());
+        }
+        egui::SidePanel::left("sidebar").resizable(true).show(ctx, |ui| {
+            self.show_sidebar(ui);
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Administrador de Archivos VRC");
 
             // Carpeta principal
             ui.horizontal(|ui| {
                 ui.label("Carpeta principal:");
-                if ui.text_edit_singleline(&mut self.root_path).changed() {
-                    path_changed = true;
-                }
-                if ui.button("📁").clicked() {
-                    if let Some(new_path) = FileDialog::new().pick_folder() {
-                        self.root_path = new_path.display().to_string();
-                        path_changed = true;
-                    }
+                ui.monospace(&self.root_path);
+                if ui
+                    .add_enabled(self.worker.is_none(), egui::Button::new("📁"))
+                    .on_hover_text("Abrir otra biblioteca; si es esta movida de sitio, conserva tags, items y versiones")
+                    .clicked()
+                {
+                    open_root = FileDialog::new().set_title("Selecciona la carpeta principal").pick_folder();
                 }
             });
 
@@ -207,26 +837,78 @@ impl eframe::App for MyApp {
             // Buscador
             ui.horizontal(|ui| {
                 ui.label("Buscar:");
-                ui.text_edit_singleline(&mut self.search_query);
-                if ui.button("🔍").clicked() {
-                    match self.store.search(&self.search_query) {
-                        Ok(res) => self.results = res,
-                        Err(e) => eprintln!("Error al buscar: {}", e),
-                    }
+                let response = ui.text_edit_singleline(&mut self.search_query);
+                if response.changed() {
+                    self.search_edited = Some(Instant::now());
+                    self.active_saved_search = None;
+                }
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("🔍").on_hover_text(SEARCH_HELP).clicked() || submitted {
+                    self.run_search();
+                }
+                if self.search_loading {
+                    ui.spinner();
                 }
             });
+            if let Some(error) = &self.search_error {
+                ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+            }
 
             ui.separator();
 
-            // Resultados de archivos
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                for file in &self.results {
-                    ui.horizontal(|ui| {
-                        ui.label(&file.name);
-                        ui.label(format!("Etiquetas: {:?}", file.tags));
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.gallery_mode, false, "☰ Lista");
+                ui.selectable_value(&mut self.gallery_mode, true, "▦ Galería");
+            });
+
+            // Resultados de archivos; solo se dibujan las filas visibles
+            let mut open_versions = None;
+            let reached_end = if self.gallery_mode {
+                show_gallery(ui, &self.results, &mut self.thumbnails, &mut open_versions)
+            } else {
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                let mut reached_end = false;
+                egui::ScrollArea::vertical()
+                    .id_salt("results")
+                    .max_height(ui.available_height() * 0.5)
+                    .auto_shrink([false, true])
+                    .show_rows(ui, row_height, self.results.len(), |ui, row_range| {
+                        reached_end = row_range.end >= self.results.len();
+                        for hit in &self.results[row_range] {
+                            ui.horizontal(|ui| {
+                                ui.label(&hit.file.name);
+                                if let Some(version) = &hit.file.version
+                                    && ui.small_button(format!("v{}", version)).on_hover_text("Historial de versiones").clicked()
+                                {
+                                    open_versions = Some(hit.file.path.clone());
+                                }
+                                ui.label(format!("Etiquetas: {:?}", hit.file.tags));
+                                if let Some(snippet) = &hit.snippet {
+                                    ui.add(egui::Label::new(snippet_job(ui, snippet)).truncate());
+                                }
+                            });
+                        }
                     });
+
+                if !self.asset_results.is_empty() {
+                    ui.separator();
+                    ui.label("Contenido de paquetes:");
+                    egui::ScrollArea::vertical()
+                        .id_salt("asset_results")
+                        .max_height(ui.available_height() * 0.3)
+                        .show_rows(ui, row_height, self.asset_results.len(), |ui, row_range| {
+                            for asset in &self.asset_results[row_range] {
+                                let package = Path::new(&asset.package).file_name().unwrap_or_default().to_string_lossy();
+                                ui.label(format!("{} — {}", asset.asset_path, package));
+                            }
+                        });
                 }
-            });
+                reached_end
+            };
+            self.load_more_results(reached_end);
+            if let Some(path) = open_versions {
+                self.show_versions(&path);
+            }
 
             ui.separator();
 
@@ -244,12 +926,19 @@ impl eframe::App for MyApp {
             ui.group(|ui| {
                 ui.label("Lista de Categorías:");
                 for cat in &self.categories {
-                    ui.label(format!("- {}", cat));
+                    ui.horizontal(|ui| {
+                        ui.add_space(cat.depth() as f32 * 16.0);
+                        ui.label(format!("- {}", cat.name));
+                    });
                 }
             });
         });
 
         // Tag Manager
+        if self.show_tag_manager && self.tag_usage.is_none() {
+            self.tag_usage = self.notifications.check("Error contando tags", self.db.tags().usage_counts());
+        }
+        let mut delete_tag = None;
         if self.show_tag_manager {
             egui::Window::new("Tag Manager")
                 .open(&mut self.show_tag_manager)
@@ -258,43 +947,91 @@ impl eframe::App for MyApp {
                     ui.horizontal(|ui| {
                         ui.text_edit_singleline(&mut self.new_tag);
                         if ui.button("➕ Add").clicked() && !self.new_tag.trim().is_empty() {
-                            let _ = self.tag_store.insert_tag(&self.new_tag);
-                            self.new_tag.clear();
-                            self.tags = self.tag_store.get_tags().unwrap_or_default();
+                            let result = self.db.tags().insert_tag(&self.new_tag);
+                            if self.notifications.check("Error creando tag", result).is_some() {
+                                self.new_tag.clear();
+                            }
+                            self.tags = self.notifications.check("Error leyendo tags", self.db.tags().get_tags()).unwrap_or_default();
                         }
                     });
                     ui.separator();
                     for tag in self.tags.clone() {
                         ui.horizontal(|ui| {
-                            if let Some((original, nuevo)) = &mut self.edit_tag {
-                                if original == &tag {
-                                    ui.text_edit_singleline(nuevo);
-                                    if ui.button("💾 Save").clicked() {
-                                        let _ = self.tag_store.update_tag(original, nuevo);
+                            if let Some((original, nuevo)) = &mut self.edit_tag && original == &tag {
+                                ui.text_edit_singleline(nuevo);
+                                if ui.button("💾 Save").clicked() {
+                                    // Si falla (p. ej. el nombre ya existe) se deja la edición abierta
+                                    let result = self.db.tags().update_tag(original, nuevo);
+                                    if self.notifications.check("Error renombrando tag", result).is_some() {
                                         self.edit_tag = None;
-                                        self.tags = self.tag_store.get_tags().unwrap_or_default();
+                                        self.tag_usage = None;
+                                        self.saved_counts = None;
                                     }
-                                    if ui.button("❌ Cancel").clicked() {
-                                        self.edit_tag = None;
-                                    }
-                                    return;
+                                    self.tags = self.notifications.check("Error leyendo tags", self.db.tags().get_tags()).unwrap_or_default();
+                                }
+                                if ui.button("❌ Cancel").clicked() {
+                                    self.edit_tag = None;
                                 }
+                                return;
                             }
 
+                            let used = self.tag_usage.as_ref().and_then(|u| u.get(&tag)).copied().unwrap_or(0);
                             ui.label(&tag);
+                            ui.weak(format!("usado por {} items", used));
                             if ui.button("✏️ Edit").clicked() {
                                 self.edit_tag = Some((tag.clone(), tag.clone()));
                             }
                             if ui.button("🗑 Delete").clicked() {
-                                let _ = self.tag_store.delete_tag(&tag);
-                                self.tags = self.tag_store.get_tags().unwrap_or_default();
+                                // Si está en uso se pide confirmación
+                                if used > 0 {
+                                    self.pending_tag_delete = Some(tag.clone());
+                                } else {
+                                    delete_tag = Some(tag.clone());
+                                }
                             }
                         });
                     }
                 });
         }
 
+        if let Some(tag) = &self.pending_tag_delete {
+            let used = self.tag_usage.as_ref().and_then(|u| u.get(tag)).copied().unwrap_or(0);
+            let mut keep_open = true;
+            egui::Window::new("Eliminar tag")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!("'{}' está en uso por {} items y se les quitará a todos.", tag, used));
+                    ui.horizontal(|ui| {
+                        if ui.button("🗑 Eliminar").clicked() {
+                            delete_tag = Some(tag.clone());
+                            keep_open = false;
+                        }
+                        if ui.button("❌ Cancel").clicked() {
+                            keep_open = false;
+                        }
+                    });
+                });
+            if !keep_open {
+                self.pending_tag_delete = None;
+            }
+        }
+        if let Some(tag) = delete_tag {
+            let result = self.db.tags().delete_tag(&tag);
+            self.notifications.check("Error eliminando tag", result);
+            self.tags = self.notifications.check("Error leyendo tags", self.db.tags().get_tags()).unwrap_or_default();
+            self.tag_usage = None;
+            self.saved_counts = None;
+            // Los resultados mostrados llevan los tags de cada carpeta
+            self.run_search();
+        }
+
         // Category Manager
+        let mut delete_requested = None;
+        let mut move_requested = None;
+        let mut create_requested = None;
+        let mut reorder_requested = None;
+        let mut cancel_new_category = false;
         if self.show_category_manager {
             egui::Window::new("Category Manager")
                 .open(&mut self.show_category_manager)
@@ -303,57 +1040,301 @@ impl eframe::App for MyApp {
                     ui.horizontal(|ui| {
                         ui.text_edit_singleline(&mut self.new_category);
                         if ui.button("➕ Add").clicked() && !self.new_category.trim().is_empty() {
-                            // Crear carpeta física
-                            let path = Path::new(&self.root_path).join(&self.new_category);
-                            if !path.exists() {
-                                if let Err(e) = fs::create_dir_all(&path) {
-                                    eprintln!("Error creando carpeta de categoría: {}", e);
-                                }
-                            }
-
-                            let _ = self.categories_store.insert_category(&self.new_category);
-                            self.new_category.clear();
-                            self.categories = self.categories_store.get_categories().unwrap_or_default();
+                            create_requested = Some((None, self.new_category.trim().to_string()));
                         }
                     });
                     ui.separator();
-                    for cat in self.categories.clone() {
-                        ui.horizontal(|ui| {
-                            if let Some((original, nuevo)) = &mut self.edit_category {
-                                if original == &cat {
+                    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                        for cat in &self.categories {
+                            ui.horizontal(|ui| {
+                                ui.add_space(cat.depth() as f32 * 16.0);
+
+                                if let Some((id, nuevo)) = &mut self.edit_category
+                                    && *id == cat.id
+                                {
                                     ui.text_edit_singleline(nuevo);
                                     if ui.button("💾 Save").clicked() {
-                                        let _ = self.categories_store.update_category(original, nuevo);
-                                        self.edit_category = None;
-                                        self.categories = self.categories_store.get_categories().unwrap_or_default();
+                                        move_requested = Some((cat.id, cat.parent_id, nuevo.trim().to_string()));
                                     }
                                     if ui.button("❌ Cancel").clicked() {
                                         self.edit_category = None;
                                     }
                                     return;
                                 }
+
+                                ui.label(if cat.depth() > 0 { format!("└ {}", cat.name) } else { cat.name.clone() });
+                                if ui.small_button("⬆").clicked() {
+                                    reorder_requested = Some((cat.id, -1));
+                                }
+                                if ui.small_button("⬇").clicked() {
+                                    reorder_requested = Some((cat.id, 1));
+                                }
+                                if ui.button("✏️ Edit").clicked() {
+                                    self.edit_category = Some((cat.id, cat.name.clone()));
+                                }
+                                if ui.button("➕ Sub").clicked() {
+                                    self.new_subcategory = Some((cat.id, String::new()));
+                                }
+                                ui.menu_button("↪ Move", |ui| {
+                                    if cat.parent_id.is_some() && ui.button("(raíz)").clicked() {
+                                        move_requested = Some((cat.id, None, cat.name.clone()));
+                                        ui.close();
+                                    }
+                                    let own_prefix = format!("{}/", cat.path);
+                                    for target in &self.categories {
+                                        let inside = target.id == cat.id || target.path.starts_with(&own_prefix);
+                                        if inside || Some(target.id) == cat.parent_id {
+                                            continue;
+                                        }
+                                        if ui.button(&target.path).clicked() {
+                                            move_requested = Some((cat.id, Some(target.id), cat.name.clone()));
+                                            ui.close();
+                                        }
+                                    }
+                                });
+                                if ui.button("🗑 Delete").clicked() {
+                                    // Se pide confirmación antes de mover la carpeta a la papelera
+                                    delete_requested = Some(cat.path.clone());
+                                }
+                            });
+
+                            if let Some((parent, name)) = &mut self.new_subcategory
+                                && *parent == cat.id
+                            {
+                                ui.horizontal(|ui| {
+                                    ui.add_space((cat.depth() + 1) as f32 * 16.0);
+                                    ui.text_edit_singleline(name);
+                                    if ui.button("💾 Save").clicked() {
+                                        create_requested = Some((Some(cat.id), name.trim().to_string()));
+                                    }
+                                    if ui.button("❌ Cancel").clicked() {
+                                        cancel_new_category = true;
+                                    }
+                                });
                             }
+                        }
+                    });
+                });
+        }
 
-                            ui.label(&cat);
-                            if ui.button("✏️ Edit").clicked() {
-                                self.edit_category = Some((cat.clone(), cat.clone()));
+        if let Some((parent, name)) = create_requested
+            && self.create_category(parent, &name)
+        {
+            if parent.is_none() {
+                self.new_category.clear();
+            }
+            cancel_new_category = true;
+        }
+        if cancel_new_category {
+            self.new_subcategory = None;
+        }
+        if let Some((id, parent, name)) = move_requested
+            && self.move_category(ctx, id, parent, &name)
+        {
+            self.edit_category = None;
+        }
+        if let Some((id, delta)) = reorder_requested {
+            self.reorder_category(id, delta);
+        }
+        if let Some(cat) = delete_requested {
+            self.pending_delete = Some(self.preview_delete(&cat));
+        }
+
+        // Confirmación de borrado de categoría
+        let mut confirm_delete = None;
+        if let Some(preview) = &self.pending_delete {
+            let mut keep_open = true;
+            egui::Window::new("Eliminar categoría")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Se moverá '{}' a la papelera ({} items, {}).",
+                        preview.category,
+                        preview.items.len(),
+                        format_size(preview.size)
+                    ));
+                    if !preview.items.is_empty() {
+                        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                            for item in &preview.items {
+                                ui.label(format!("- {}", item));
                             }
-                            if ui.button("🗑 Delete").clicked() {
-                                // Borrar carpeta física
-                                let path = Path::new(&self.root_path).join(&cat);
-                                if path.exists() {
-                                    if let Err(e) = fs::remove_dir_all(&path) {
-                                        eprintln!("Error eliminando carpeta: {}", e);
-                                    }
+                        });
+                    }
+                    ui.label(format!("Podrás restaurarla durante {} días.", self.trash_retention_days));
+                    ui.horizontal(|ui| {
+                        if ui.button("🗑 Mover a la papelera").clicked() {
+                            confirm_delete = Some(preview.category.clone());
+                            keep_open = false;
+                        }
+                        if ui.button("❌ Cancel").clicked() {
+                            keep_open = false;
+                        }
+                    });
+                });
+            if !keep_open {
+                self.pending_delete = None;
+            }
+        }
+        if let Some(cat) = confirm_delete {
+            self.delete_category(ctx, &cat);
+        }
+
+        // Papelera
+        let mut restore = None;
+        let mut purge = None;
+        let mut retention_changed = false;
+        if self.show_trash {
+            egui::Window::new("Papelera")
+                .open(&mut self.show_trash)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Conservar durante");
+                        retention_changed = ui
+                            .add(egui::DragValue::new(&mut self.trash_retention_days).range(1..=3650).suffix(" días"))
+                            .changed();
+                    });
+                    ui.separator();
+                    if self.trash_entries.is_empty() {
+                        ui.weak("La papelera está vacía");
+                    }
+                    for entry in &self.trash_entries {
+                        ui.horizontal(|ui| {
+                            let deleted = chrono::DateTime::from_timestamp(entry.deleted_at, 0)
+                                .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                                .unwrap_or_default();
+                            ui.label(&entry.original);
+                            ui.weak(deleted);
+                            if ui.button("♻ Restaurar").clicked() {
+                                restore = Some(entry.clone());
+                            }
+                            if ui.button("🗑 Eliminar definitivamente").clicked() {
+                                purge = Some(entry.clone());
+                            }
+                        });
+                    }
+                });
+        }
+        if retention_changed {
+            self.save_config();
+        }
+        if let Some(entry) = restore {
+            self.restore_from_trash(ctx, &entry);
+        }
+        if let Some(entry) = purge {
+            self.purge_trash_entry(&entry);
+        }
+
+        // Duplicados
+        let mut keep_requested = None;
+        if self.show_duplicates {
+            let worker_busy = self.worker.is_some();
+            let searching = worker_busy && self.duplicates.is_empty();
+            egui::Window::new("Duplicados")
+                .open(&mut self.show_duplicates)
+                .show(ctx, |ui| {
+                    if searching {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Calculando hashes…");
+                        });
+                    } else if self.duplicates.is_empty() {
+                        ui.weak("No hay archivos duplicados");
+                    } else {
+                        let wasted: u64 = self.duplicates.iter().map(|g| g.wasted()).sum();
+                        ui.label(format!("{} grupos; {} recuperables", self.duplicates.len(), format_size(wasted)));
+                    }
+                    ui.separator();
+
+                    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                        for group in &self.duplicates {
+                            let keep = self.duplicate_keep.entry(group.hash.clone()).or_insert(0);
+                            ui.group(|ui| {
+                                ui.label(format!("{} copias de {}", group.paths.len(), format_size(group.size)));
+                                for (i, path) in group.paths.iter().enumerate() {
+                                    ui.radio_value(keep, i, path);
+                                }
+                                let button = egui::Button::new("Conservar la marcada y enviar las demás a la papelera");
+                                if ui.add_enabled(!worker_busy, button).clicked() {
+                                    keep_requested = Some((group.clone(), *keep));
                                 }
+                            });
+                        }
+                    });
 
-                                let _ = self.categories_store.delete_category(&cat);
-                                self.categories = self.categories_store.get_categories().unwrap_or_default();
+                    if !self.file_links.is_empty() {
+                        ui.collapsing(format!("Copias reemplazadas ({})", self.file_links.len()), |ui| {
+                            for link in &self.file_links {
+                                ui.label(format!("{} → {}", link.path, link.target));
                             }
                         });
                     }
                 });
         }
+        if let Some((group, keep)) = keep_requested {
+            self.keep_duplicate(&group, keep);
+        }
+
+        // Historial de versiones
+        let mut versions_open = self.item_versions.is_some();
+        let mut save_changelog = None;
+        if let Some((item_path, versions)) = &self.item_versions {
+            let name = Path::new(item_path).file_name().unwrap_or_default().to_string_lossy();
+            egui::Window::new(format!("Versiones de {}", name))
+                .id(egui::Id::new("item_versions"))
+                .open(&mut versions_open)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                        for v in versions {
+                            ui.group(|ui| {
+                                ui.horizontal(|ui| {
+                                    let label = egui::RichText::new(format!("v{}", v.version)).strong();
+                                    ui.label(if v.current { label.color(ui.visuals().hyperlink_color) } else { label });
+                                    if v.current {
+                                        ui.small("(actual)");
+                                    }
+                                    let imported = chrono::DateTime::from_timestamp(v.imported_at, 0)
+                                        .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                                        .unwrap_or_default();
+                                    ui.weak(imported);
+                                });
+                                let file = Path::new(&v.file_path).file_name().unwrap_or_default().to_string_lossy();
+                                ui.label(file).on_hover_text(&v.file_path);
+
+                                match &mut self.edit_changelog {
+                                    Some((id, text)) if *id == v.id => {
+                                        ui.add(egui::TextEdit::multiline(text).desired_rows(3));
+                                        if ui.button("Guardar notas").clicked() {
+                                            save_changelog = Some((v.id, text.trim().to_string()));
+                                        }
+                                    }
+                                    _ => {
+                                        if let Some(changelog) = &v.changelog {
+                                            ui.label(changelog);
+                                        }
+                                        if ui.small_button("✏ Notas").clicked() {
+                                            self.edit_changelog = Some((v.id, v.changelog.clone().unwrap_or_default()));
+                                        }
+                                    }
+                                }
+                            });
+                        }
+                    });
+                });
+        }
+        if let Some((id, text)) = save_changelog {
+            let changelog = Some(text.as_str()).filter(|t| !t.is_empty());
+            let result = self.db.files().set_version_changelog(id, changelog);
+            if self.notifications.check("Error guardando las notas de la versión", result).is_some()
+                && let Some((item_path, _)) = self.item_versions.clone()
+            {
+                self.show_versions(&item_path);
+            }
+        }
+        if !versions_open {
+            self.item_versions = None;
+            self.edit_changelog = None;
+        }
 
 				// Item Manager
         if self.show_item_manager {
@@ -368,10 +1349,10 @@ impl eframe::App for MyApp {
 						.show_ui(ui, |ui| {
 							for cat in &self.categories {
 								if ui.selectable_label(
-									Some(cat.clone()) == self.selected_category,
-									cat
+									Some(&cat.path) == self.selected_category.as_ref(),
+									&cat.path
 								).clicked() {
-									self.selected_category = Some(cat.clone());
+									self.selected_category = Some(cat.path.clone());
 								}
 							}
 						});
@@ -382,6 +1363,14 @@ impl eframe::App for MyApp {
 							ui.text_edit_singleline(&mut self.new_item_name);
 						});
 
+						ui.label("Notas:");
+						ui.add(egui::TextEdit::multiline(&mut self.new_item_notes).desired_rows(3));
+
+						// Con el nombre de un item que ya existe en la categoría, los archivos se añaden como versión nueva
+						ui.label("Notas de la versión:");
+						ui.add(egui::TextEdit::multiline(&mut self.new_item_changelog).desired_rows(2))
+							.on_hover_text("Si ya hay un item con este nombre en la categoría, los archivos se añaden como una versión nueva");
+
 						// Seleccionar tags existentes o crear
 						ui.label("Tags:");
 						for tag in &self.tags {
@@ -401,97 +1390,258 @@ impl eframe::App for MyApp {
 						ui.horizontal(|ui| {
 							ui.text_edit_singleline(&mut new_tag_temp);
 							if ui.button("➕ Add Tag").clicked() && !new_tag_temp.trim().is_empty() {
-								let _ = self.tag_store.insert_tag(&new_tag_temp);
-								self.tags = self.tag_store.get_tags().unwrap_or_default();
-								self.selected_tags.push(new_tag_temp.clone());
+								let result = self.db.tags().insert_tag(&new_tag_temp);
+								if self.notifications.check("Error creando tag", result).is_some() {
+									self.selected_tags.push(new_tag_temp.clone());
+								}
+								self.tags = self.notifications.check("Error leyendo tags", self.db.tags().get_tags()).unwrap_or_default();
 								new_tag_temp.clear();
 							}
 						});
 
-						// Seleccionar archivo de datos
-						if ui.button("Seleccionar archivo de datos").clicked() {
-							if let Some(path) = FileDialog::new().pick_file() {
-								self.item_file_path = Some(path.display().to_string());
+						// Seleccionar archivos de datos (se guardan con su nombre original)
+						let mut selection_changed = false;
+						if ui.button("Seleccionar archivos de datos").clicked() && let Some(paths) = FileDialog::new().pick_files() {
+							for path in paths {
+								let path = path.display().to_string();
+								if !self.item_file_paths.contains(&path) {
+									self.item_file_paths.push(path);
+								}
 							}
+							selection_changed = true;
 						}
 
-						if let Some(path) = &self.item_file_path {
-							ui.label(format!("Archivo seleccionado: {}", path));
+						let mut remove_file = None;
+						for (i, path) in self.item_file_paths.iter().enumerate() {
+							ui.horizontal(|ui| {
+								ui.label(format!("Archivo seleccionado: {}", path));
+								if ui.small_button("❌").clicked() {
+									remove_file = Some(i);
+								}
+							});
+						}
+						if let Some(i) = remove_file {
+							self.item_file_paths.remove(i);
+							selection_changed = true;
+						}
+						if selection_changed {
+							self.item_is_package = self.item_file_paths.iter().any(|p| unitypackage::is_unitypackage(Path::new(p)));
+						}
+
+						// Los unitypackage suelen traer su propia vista previa
+						if self.item_is_package {
+							ui.checkbox(&mut self.use_package_preview, "Usar la vista previa del paquete (si tiene)");
 						}
 
 						// Seleccionar imagen
-						if ui.button("Seleccionar imagen de referencia").clicked() {
-							if let Some(path) = FileDialog::new().pick_file() {
-								self.item_image_path = Some(path.display().to_string());
-							}
+						if ui.button("Seleccionar imagen de referencia").clicked() && let Some(path) = FileDialog::new().pick_file() {
+							self.item_image_path = Some(path.display().to_string());
 						}
 
 						if let Some(path) = &self.item_image_path {
 							ui.label(format!("Imagen seleccionada: {}", path));
 						}
 
-						// Botón guardar item
-						if ui.button("Guardar Item").clicked() {
+						egui::ComboBox::from_label("Si el archivo ya existe")
+						.selected_text(self.collision_policy.label())
+						.show_ui(ui, |ui| {
+							for policy in CollisionPolicy::ALL {
+								ui.selectable_value(&mut self.collision_policy, policy, policy.label());
+							}
+						});
+
+						// Botón guardar item (la copia y el guardado en DB se hacen en segundo plano)
+						if ui.add_enabled(self.worker.is_none(), egui::Button::new("Guardar Item")).clicked() {
 							if let Some(category) = &self.selected_category {
+								let preview_from_package = self.item_is_package && self.use_package_preview;
 								if !self.new_item_name.trim().is_empty() &&
-									self.item_file_path.is_some() &&
-									self.item_image_path.is_some()
+									!self.item_file_paths.is_empty() &&
+									(self.item_image_path.is_some() || preview_from_package)
 								{
-									let item_path = Path::new(&self.root_path).join(category).join(&self.new_item_name);
-									if let Err(e) = fs::create_dir_all(&item_path) {
-										eprintln!("Error creando carpeta del item: {}", e);
-									}
-
-									// Copiar archivos seleccionados a la carpeta del item
-									let _ = fs::copy(
-										self.item_file_path.as_ref().unwrap(),
-										item_path.join("data.txt") // o mantener el nombre original
-									);
-
-									let _ = fs::copy(
-										self.item_image_path.as_ref().unwrap(),
-										item_path.join("image.png") // o mantener la extensión
-									);
-
-									// Aquí podrías guardar los datos del item en la DB si quieres
-									// por ejemplo con tabla items(id, name, category, tags_json, file_path, image_path)
+									let item = Item {
+										name: self.new_item_name.trim().to_string(),
+										category: category.clone(),
+										tags: self.selected_tags.clone(),
+										notes: Some(self.new_item_notes.trim().to_string()).filter(|n| !n.is_empty()),
+										..Default::default()
+									};
+									let task = Task::ImportItem {
+										item: Box::new(item),
+										root: PathBuf::from(&self.root_path),
+										data_srcs: self.item_file_paths.iter().map(PathBuf::from).collect(),
+										image_src: self.item_image_path.as_ref().map(PathBuf::from),
+										use_package_preview: preview_from_package,
+										collision: self.collision_policy,
+										changelog: Some(self.new_item_changelog.trim().to_string()).filter(|c| !c.is_empty()),
+									};
+									self.worker = Some(Worker::spawn(&self.library.db(), task, repaint(ctx)));
 
 									self.new_item_name.clear();
+									self.new_item_notes.clear();
+									self.new_item_changelog.clear();
 									self.selected_tags.clear();
-									self.item_file_path = None;
+									self.item_file_paths.clear();
 									self.item_image_path = None;
+									self.item_is_package = false;
+								} else {
+									self.notifications.warn("Falta el nombre, los archivos de datos o la imagen del item");
 								}
+							} else {
+								self.notifications.warn("Selecciona una categoría para el item");
 							}
 						}
 					});
 				}
 
-        if path_changed {
-            self.save_config();
-            self.sync_categories_with_fs();
+        if let Some(root) = open_root {
+            self.open_library(root, ctx);
         }
+
+        self.notifications.show_toasts(ctx);
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let options = eframe::NativeOptions::default();
+/// Aviso para el hilo de trabajo: repintar la interfaz cuando mande algo
+fn repaint(ctx: &egui::Context) -> impl Fn() + Send + 'static {
+    let ctx = ctx.clone();
+    move || ctx.request_repaint()
+}
+
+/// Tamaño legible, p. ej. `12.3 MB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, UNITS[unit]) }
+}
+
+/// Fragmento de un resultado con las coincidencias resaltadas
+fn snippet_job(ui: &egui::Ui, snippet: &str) -> egui::text::LayoutJob {
+    let normal = egui::TextFormat {
+        font_id: egui::TextStyle::Small.resolve(ui.style()),
+        color: ui.visuals().weak_text_color(),
+        ..Default::default()
+    };
+    let highlighted = egui::TextFormat {
+        color: ui.visuals().strong_text_color(),
+        background: ui.visuals().selection.bg_fill,
+        ..normal.clone()
+    };
+
+    let mut job = egui::text::LayoutJob::default();
+    let mut rest = snippet;
+    while let Some(start) = rest.find(MATCH_START) {
+        job.append(&rest[..start], 0.0, normal.clone());
+        rest = &rest[start + MATCH_START.len_utf8()..];
+        let end = rest.find(MATCH_END).unwrap_or(rest.len());
+        job.append(&rest[..end], 0.0, highlighted.clone());
+        rest = rest.get(end + MATCH_END.len_utf8()..).unwrap_or_default();
+    }
+    job.append(rest, 0.0, normal);
+    job
+}
+
+/// Resultados como tarjetas con miniatura. Solo se dibujan (y cargan) las filas visibles.
+/// Devuelve si la última fila está a la vista; `open_versions` recibe la carpeta cuyo historial de
+/// versiones se pidió.
+fn show_gallery(
+    ui: &mut egui::Ui,
+    results: &[SearchHit],
+    thumbnails: &mut ThumbnailCache,
+    open_versions: &mut Option<String>,
+) -> bool {
+    let spacing = ui.spacing().item_spacing;
+    let card_outer = egui::vec2(CARD_WIDTH, CARD_HEIGHT) + egui::vec2(16.0, 16.0);
+    let columns = ((ui.available_width() + spacing.x) / (card_outer.x + spacing.x)).floor().max(1.0) as usize;
+    let rows = results.len().div_ceil(columns);
+
+    let mut reached_end = false;
+    egui::ScrollArea::vertical()
+        .id_salt("gallery")
+        .max_height(ui.available_height() * 0.7)
+        .show_rows(ui, card_outer.y, rows, |ui, row_range| {
+            reached_end = row_range.end >= rows;
+            for row in row_range {
+                ui.horizontal(|ui| {
+                    for hit in results.iter().skip(row * columns).take(columns) {
+                        if item_card(ui, hit, thumbnails) {
+                            *open_versions = Some(hit.file.path.clone());
+                        }
+                    }
+                });
+            }
+        });
+    reached_end
+}
 
-    // 🔹 Dos conexiones independientes para evitar moves
-    let conn1 = rusqlite::Connection::open("files.db")?;
-    let conn2 = rusqlite::Connection::open("files.db")?;
+/// Devuelve si se pulsó la versión para ver el historial
+fn item_card(ui: &mut egui::Ui, hit: &SearchHit, thumbnails: &mut ThumbnailCache) -> bool {
+    let file = &hit.file;
+    let item_dir = Path::new(&file.path);
+    let category = item_dir
+        .parent()
+        .and_then(|p| p.file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut versions_clicked = false;
+    egui::Frame::group(ui.style()).show(ui, |ui| {
+        ui.set_width(CARD_WIDTH);
+        ui.set_height(CARD_HEIGHT);
+        ui.vertical(|ui| {
+            let image_size = egui::vec2(CARD_WIDTH, CARD_WIDTH);
+            ui.allocate_ui_with_layout(image_size, egui::Layout::centered_and_justified(egui::Direction::TopDown), |ui| {
+                match thumbnails.get_for_item(item_dir) {
+                    Some(texture) => {
+                        ui.add(egui::Image::from_texture(&texture).max_size(image_size));
+                    }
+                    None => {
+                        ui.weak("Sin imagen");
+                    }
+                }
+            });
 
-    let store = Store::new("files.db")?;
+            ui.add(egui::Label::new(egui::RichText::new(&file.name).strong()).truncate());
+            ui.horizontal(|ui| {
+                ui.small(category);
+                if let Some(version) = &file.version {
+                    versions_clicked = ui.small_button(format!("v{}", version)).on_hover_text("Historial de versiones").clicked();
+                }
+            });
+            if let Some(snippet) = &hit.snippet {
+                ui.add(egui::Label::new(snippet_job(ui, snippet)).truncate());
+            }
+            ui.horizontal_wrapped(|ui| {
+                for tag in &file.tags {
+                    egui::Frame::new()
+                        .fill(ui.visuals().faint_bg_color)
+                        .corner_radius(8.0)
+                        .inner_margin(egui::Margin::symmetric(6, 2))
+                        .show(ui, |ui| {
+                            ui.small(tag);
+                        });
+                }
+            });
+        });
+    });
+    versions_clicked
+}
 
-    let tag_store = TagStore::new(conn1);
-    tag_store.init()?; // crear tabla de tags
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = eframe::NativeOptions::default();
 
-    let categories_store = CategoryStore::new(conn2);
-    categories_store.init()?; // crear tabla de categorías
+    // La base va dentro de la biblioteca, así que primero hay que saber cuál abrir
+    let library = LibraryPaths::new(MyApp::load_or_create_config().root_path);
+    let opened = Database::open_library(&library)?;
 
     eframe::run_native(
         "Administrador de Archivos VRC",
         options,
-        Box::new(|_cc| Ok(Box::new(MyApp::new(store, tag_store, categories_store)))),
+        Box::new(|cc| Ok(Box::new(MyApp::new(&cc.egui_ctx, library, opened)))),
     )?;
 
     Ok(())
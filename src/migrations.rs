@@ -0,0 +1,824 @@
+use super::error::{StoreError, StoreResult};
+use rusqlite::{params, Connection, Transaction};
+use std::path::{Path, PathBuf};
+
+/// Pasos del esquema de la base en orden: el paso `i` lleva la base de la versión `i` a la `i + 1`.
+/// La versión se guarda en `PRAGMA user_version`; un paso publicado no se modifica, los cambios
+/// nuevos van en un paso nuevo al final. Cada paso recibe la carpeta principal de la biblioteca.
+const MIGRATIONS: &[fn(&Transaction, &Path) -> StoreResult<()>] = &[baseline, foreign_keys, full_text_search, added_at, saved_searches, file_hashes, item_versions, relative_paths, drop_item_subcategory, keep_orphan_items];
+
+/// Versión del esquema que espera esta versión del programa
+pub const LATEST_VERSION: i64 = MIGRATIONS.len() as i64;
+
+pub fn schema_version(conn: &Connection) -> StoreResult<i64> {
+    Ok(conn.query_row("PRAGMA user_version", [], |r| r.get(0))?)
+}
+
+/// Aplica las migraciones pendientes de la base en `db_path`, que es la de la biblioteca con
+/// carpeta principal `root`.
+///
+/// Antes de modificar una base que ya tenía datos se guarda una copia como
+/// `<db_path>.v<versión>.bak`; devuelve su ruta si se hizo. Cada paso va en su propia
+/// transacción, así que un fallo deja la base en la última versión completa.
+pub fn run(db_path: &str, root: &Path) -> StoreResult<Option<PathBuf>> {
+    let mut conn = Connection::open(db_path)?;
+    let version = schema_version(&conn)?;
+    if version > LATEST_VERSION {
+        return Err(StoreError::Migration(format!(
+            "la base es de la versión {} y este programa solo conoce hasta la {}",
+            version, LATEST_VERSION
+        )));
+    }
+    if version == LATEST_VERSION {
+        return Ok(None);
+    }
+
+    let backup = if has_tables(&conn)? { Some(backup(&conn, db_path, version)?) } else { None };
+
+    for (i, step) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+        step(&tx, root).map_err(|e| StoreError::Migration(format!("paso {} -> {}: {}", i, i + 1, e)))?;
+        tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+        tx.commit()?;
+    }
+    Ok(backup)
+}
+
+/// Copia consistente de la base antes de migrarla
+fn backup(conn: &Connection, db_path: &str, version: i64) -> StoreResult<PathBuf> {
+    let mut target = PathBuf::from(format!("{}.v{}.bak", db_path, version));
+    let mut n = 2;
+    while target.exists() {
+        target = PathBuf::from(format!("{}.v{}.{}.bak", db_path, version, n));
+        n += 1;
+    }
+    conn.execute("VACUUM INTO ?1", params![target.display().to_string()])?;
+    Ok(target)
+}
+
+fn has_tables(conn: &Connection) -> StoreResult<bool> {
+    let count: i64 = conn.query_row("SELECT count(*) FROM sqlite_master WHERE type = 'table'", [], |r| r.get(0))?;
+    Ok(count > 0)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> StoreResult<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        params![table],
+        |r| r.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> StoreResult<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |r| r.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// 0 -> 1: las bases sin versión se crearon tabla a tabla por cada store, así que pueden estar en
+/// cualquier estado intermedio. Este paso las deja todas con el mismo esquema.
+fn baseline(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS files (
+            path TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT '[]',
+            size INTEGER NOT NULL DEFAULT 0,
+            mtime INTEGER NOT NULL DEFAULT 0,
+            hash TEXT
+        );
+        CREATE TABLE IF NOT EXISTS package_assets (
+            item_path TEXT NOT NULL,
+            package TEXT NOT NULL,
+            guid TEXT NOT NULL,
+            asset_path TEXT NOT NULL,
+            size INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (package, guid)
+        );
+        CREATE INDEX IF NOT EXISTS idx_package_assets_item ON package_assets(item_path);
+        CREATE INDEX IF NOT EXISTS idx_package_assets_path ON package_assets(asset_path);
+        CREATE TABLE IF NOT EXISTS tags (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL);
+        "#,
+    )?;
+
+    // Bases creadas antes de guardar metadatos no tienen estas columnas
+    for (column, definition) in [
+        ("size", "INTEGER NOT NULL DEFAULT 0"),
+        ("mtime", "INTEGER NOT NULL DEFAULT 0"),
+        ("hash", "TEXT"),
+    ] {
+        if !column_exists(tx, "files", column)? {
+            tx.execute_batch(&format!("ALTER TABLE files ADD COLUMN {} {};", column, definition))?;
+        }
+    }
+
+    // Antes las categorías eran `categories(name)` + `subcategories(category, name)`
+    let flat_categories = table_exists(tx, "categories")? && !column_exists(tx, "categories", "id")?;
+    if flat_categories {
+        tx.execute_batch("ALTER TABLE categories RENAME TO categories_old;")?;
+    }
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER REFERENCES categories(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            sort_order INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_categories_name ON categories(ifnull(parent_id, 0), name);
+        "#,
+    )?;
+    if flat_categories {
+        tx.execute_batch(
+            r#"
+            INSERT INTO categories (parent_id, name, sort_order)
+                SELECT NULL, name, ROW_NUMBER() OVER (ORDER BY name) FROM categories_old;
+            DROP TABLE categories_old;
+            "#,
+        )?;
+    }
+    if table_exists(tx, "subcategories")? {
+        tx.execute_batch(
+            r#"
+            INSERT OR IGNORE INTO categories (parent_id, name, sort_order)
+                SELECT c.id, s.name, ROW_NUMBER() OVER (PARTITION BY s.category ORDER BY s.name)
+                FROM subcategories s JOIN categories c ON c.parent_id IS NULL AND c.name = s.category
+                GROUP BY s.category, s.name;
+            DROP TABLE subcategories;
+            "#,
+        )?;
+    }
+
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            category TEXT NOT NULL,
+            subcategory TEXT,
+            file_path TEXT NOT NULL,
+            image_path TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS item_tags (
+            item_id INTEGER NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (item_id, tag_id)
+        );
+        CREATE TABLE IF NOT EXISTS item_files (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            item_id INTEGER NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+            path TEXT NOT NULL,
+            role TEXT NOT NULL
+        );
+
+        -- Los items guardaban la subcategoría aparte; ahora la categoría es la ruta completa
+        UPDATE items SET category = category || '/' || subcategory, subcategory = NULL
+        WHERE subcategory IS NOT NULL AND subcategory <> '';
+        "#,
+    )?;
+    Ok(())
+}
+
+/// 1 -> 2: los tags de `files` pasan de JSON a la tabla `file_tags`, y las relaciones entre tablas
+/// llevan claves foráneas para que renombrar o borrar un tag o una categoría no deje restos.
+/// Las filas que ya apuntaban a algo inexistente se descartan.
+fn foreign_keys(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    tx.execute_batch(
+        r#"
+        INSERT OR IGNORE INTO tags (name)
+            SELECT DISTINCT j.value
+            FROM files f, json_each(CASE WHEN json_valid(f.tags) THEN f.tags ELSE '[]' END) j
+            WHERE j.type = 'text' AND j.value <> '';
+
+        CREATE TABLE file_tags (
+            file_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE ON UPDATE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (file_path, tag_id)
+        );
+        CREATE INDEX idx_file_tags_tag ON file_tags(tag_id);
+        INSERT OR IGNORE INTO file_tags (file_path, tag_id)
+            SELECT f.path, t.id
+            FROM files f, json_each(CASE WHEN json_valid(f.tags) THEN f.tags ELSE '[]' END) j
+            JOIN tags t ON t.name = j.value;
+        ALTER TABLE files DROP COLUMN tags;
+
+        CREATE TABLE package_assets_new (
+            item_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE ON UPDATE CASCADE,
+            package TEXT NOT NULL,
+            guid TEXT NOT NULL,
+            asset_path TEXT NOT NULL,
+            size INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (package, guid)
+        );
+        INSERT INTO package_assets_new (item_path, package, guid, asset_path, size)
+            SELECT item_path, package, guid, asset_path, size FROM package_assets
+            WHERE item_path IN (SELECT path FROM files);
+        DROP TABLE package_assets;
+        ALTER TABLE package_assets_new RENAME TO package_assets;
+        CREATE INDEX idx_package_assets_item ON package_assets(item_path);
+        CREATE INDEX idx_package_assets_path ON package_assets(asset_path);
+
+        -- `category` se queda con la ruta para mostrarla; `category_id` es la relación
+        ALTER TABLE items ADD COLUMN category_id INTEGER REFERENCES categories(id) ON DELETE CASCADE;
+        WITH RECURSIVE tree(id, path) AS (
+            SELECT id, name FROM categories WHERE parent_id IS NULL
+            UNION ALL
+            SELECT c.id, tree.path || '/' || c.name FROM categories c JOIN tree ON c.parent_id = tree.id
+        )
+        UPDATE items SET category_id = (SELECT id FROM tree WHERE tree.path = items.category);
+
+        DELETE FROM item_tags WHERE tag_id NOT IN (SELECT id FROM tags) OR item_id NOT IN (SELECT id FROM items);
+        DELETE FROM item_files WHERE item_id NOT IN (SELECT id FROM items);
+        DELETE FROM categories WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM categories);
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Columnas de `files_fts` calculadas para la fila de `files`. La categoría es la ruta más larga
+/// del árbol con la que termina la carpeta padre; las notas son las de los items guardados dentro.
+const FILES_FTS_COLUMNS: &str = r#"
+    files.rowid, files.name,
+    (SELECT group_concat(t.name, ' ') FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_path = files.path),
+    (WITH RECURSIVE tree(id, path) AS (
+        SELECT id, name FROM categories WHERE parent_id IS NULL
+        UNION ALL
+        SELECT c.id, tree.path || '/' || c.name FROM categories c JOIN tree ON c.parent_id = tree.id
+     )
+     SELECT path FROM tree
+     WHERE substr(replace(files.path, '\', '/'), -(length(path) + length(files.name) + 2)) = '/' || path || '/' || files.name
+     ORDER BY length(path) DESC LIMIT 1),
+    (SELECT group_concat(i.notes, ' ') FROM items i
+     WHERE substr(i.file_path, 1, length(files.path) + 1) IN (files.path || '/', files.path || '\'))
+"#;
+
+/// Vuelve a calcular las filas de `files_fts` de las carpetas cuyo rowid devuelve `rowids`
+fn reindex_files(rowids: &str) -> String {
+    format!(
+        "DELETE FROM files_fts WHERE rowid IN ({rowids});
+         INSERT INTO files_fts (rowid, name, tags, category, notes) SELECT {FILES_FTS_COLUMNS} FROM files WHERE rowid IN ({rowids});"
+    )
+}
+
+/// 2 -> 3: índices FTS5 para la búsqueda. `files_fts` tiene una fila por carpeta de item (mismo
+/// rowid que en `files`) con su nombre, tags, categoría y notas; `assets_fts` indexa las rutas de
+/// `package_assets` sin copiarlas. Los triggers los mantienen al día con cada cambio.
+fn full_text_search(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    let item_folder = |item: &str| {
+        format!(
+            "SELECT rowid FROM files WHERE substr({item}.file_path, 1, length(path) + 1) IN (path || '/', path || '\\')"
+        )
+    };
+    let category_folders = "SELECT rowid FROM files WHERE instr(replace(path, '\\', '/'), '/' || NEW.name || '/') > 0";
+    let tagged_folders = "SELECT f.rowid FROM files f JOIN file_tags ft ON ft.file_path = f.path WHERE ft.tag_id = NEW.id";
+
+    let sql = format!(
+        r#"
+        ALTER TABLE items ADD COLUMN notes TEXT;
+
+        CREATE VIRTUAL TABLE files_fts USING fts5(
+            name, tags, category, notes,
+            tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'
+        );
+        CREATE VIRTUAL TABLE assets_fts USING fts5(
+            asset_path, content = 'package_assets',
+            tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'
+        );
+
+        CREATE TRIGGER files_fts_insert AFTER INSERT ON files BEGIN
+            {new_file}
+        END;
+        CREATE TRIGGER files_fts_update AFTER UPDATE OF path, name ON files BEGIN
+            {new_file}
+        END;
+        CREATE TRIGGER files_fts_delete AFTER DELETE ON files BEGIN
+            DELETE FROM files_fts WHERE rowid = OLD.rowid;
+        END;
+
+        CREATE TRIGGER file_tags_fts_insert AFTER INSERT ON file_tags BEGIN
+            {new_file_tag}
+        END;
+        CREATE TRIGGER file_tags_fts_update AFTER UPDATE ON file_tags BEGIN
+            {new_file_tag}
+        END;
+        CREATE TRIGGER file_tags_fts_delete AFTER DELETE ON file_tags BEGIN
+            {old_file_tag}
+        END;
+        CREATE TRIGGER tags_fts_update AFTER UPDATE OF name ON tags BEGIN
+            {tag}
+        END;
+
+        CREATE TRIGGER items_fts_insert AFTER INSERT ON items BEGIN
+            {new_item}
+        END;
+        CREATE TRIGGER items_fts_update AFTER UPDATE OF file_path, notes ON items BEGIN
+            {old_item}
+            {new_item}
+        END;
+        CREATE TRIGGER items_fts_delete AFTER DELETE ON items BEGIN
+            {old_item}
+        END;
+
+        CREATE TRIGGER categories_fts_insert AFTER INSERT ON categories BEGIN
+            {category}
+        END;
+        CREATE TRIGGER categories_fts_update AFTER UPDATE OF parent_id, name ON categories BEGIN
+            {category}
+        END;
+
+        CREATE TRIGGER package_assets_fts_insert AFTER INSERT ON package_assets BEGIN
+            INSERT INTO assets_fts (rowid, asset_path) VALUES (NEW.rowid, NEW.asset_path);
+        END;
+        CREATE TRIGGER package_assets_fts_update AFTER UPDATE OF asset_path ON package_assets BEGIN
+            INSERT INTO assets_fts (assets_fts, rowid, asset_path) VALUES ('delete', OLD.rowid, OLD.asset_path);
+            INSERT INTO assets_fts (rowid, asset_path) VALUES (NEW.rowid, NEW.asset_path);
+        END;
+        CREATE TRIGGER package_assets_fts_delete AFTER DELETE ON package_assets BEGIN
+            INSERT INTO assets_fts (assets_fts, rowid, asset_path) VALUES ('delete', OLD.rowid, OLD.asset_path);
+        END;
+
+        INSERT INTO assets_fts (assets_fts) VALUES ('rebuild');
+        INSERT INTO files_fts (rowid, name, tags, category, notes) SELECT {FILES_FTS_COLUMNS} FROM files;
+        "#,
+        new_file = reindex_files("NEW.rowid"),
+        new_file_tag = reindex_files("SELECT rowid FROM files WHERE path = NEW.file_path"),
+        old_file_tag = reindex_files("SELECT rowid FROM files WHERE path = OLD.file_path"),
+        tag = reindex_files(tagged_folders),
+        new_item = reindex_files(&item_folder("NEW")),
+        old_item = reindex_files(&item_folder("OLD")),
+        category = reindex_files(category_folders),
+    );
+    tx.execute_batch(&sql)?;
+    Ok(())
+}
+
+/// 3 -> 4: fecha en que se indexó cada carpeta, para `added:` en el buscador. Las que ya estaban
+/// toman su fecha de modificación.
+fn added_at(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    tx.execute_batch(
+        r#"
+        ALTER TABLE files ADD COLUMN added_at INTEGER NOT NULL DEFAULT 0;
+        UPDATE files SET added_at = mtime;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// 4 -> 5: búsquedas guardadas; se guarda el texto del buscador tal cual y se vuelve a interpretar
+/// cada vez que se usa
+fn saved_searches(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE saved_searches (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE NOT NULL,
+            query TEXT NOT NULL,
+            sort_order INTEGER NOT NULL DEFAULT 0
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+/// 5 -> 6: hash de cada archivo de las carpetas de item para encontrar duplicados, y las copias que
+/// se eliminaron apuntando a la que se conservó
+fn file_hashes(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE file_hashes (
+            path TEXT PRIMARY KEY,
+            item_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE ON UPDATE CASCADE,
+            size INTEGER NOT NULL,
+            mtime INTEGER NOT NULL,
+            hash TEXT NOT NULL
+        );
+        CREATE INDEX idx_file_hashes_hash ON file_hashes(hash);
+        CREATE INDEX idx_file_hashes_item ON file_hashes(item_path);
+
+        CREATE TABLE file_links (
+            path TEXT PRIMARY KEY,
+            item_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE ON UPDATE CASCADE,
+            target TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+/// 6 -> 7: versiones de cada item. `is_current` marca la más nueva y la mantiene `SqliteStore`,
+/// porque el orden de versiones (`1.10` > `1.9`) no es el de SQL. Las carpetas ya indexadas se
+/// marcan como modificadas para que el próximo escaneo tome sus versiones de los paquetes en disco.
+fn item_versions(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE item_versions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            item_path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE ON UPDATE CASCADE,
+            file_path TEXT NOT NULL,
+            version TEXT NOT NULL,
+            imported_at INTEGER NOT NULL,
+            changelog TEXT,
+            is_current INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(item_path, file_path)
+        );
+        UPDATE files SET mtime = 0;
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Columnas de `files_fts` desde el paso 7 -> 8: como [`FILES_FTS_COLUMNS`], pero se antepone `/` a
+/// la ruta para que la categoría también se encuentre con rutas relativas a la biblioteca
+const RELATIVE_FILES_FTS_COLUMNS: &str = r#"
+    files.rowid, files.name,
+    (SELECT group_concat(t.name, ' ') FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_path = files.path),
+    (WITH RECURSIVE tree(id, path) AS (
+        SELECT id, name FROM categories WHERE parent_id IS NULL
+        UNION ALL
+        SELECT c.id, tree.path || '/' || c.name FROM categories c JOIN tree ON c.parent_id = tree.id
+     )
+     SELECT path FROM tree
+     WHERE substr('/' || replace(files.path, '\', '/'), -(length(path) + length(files.name) + 2)) = '/' || path || '/' || files.name
+     ORDER BY length(path) DESC LIMIT 1),
+    (SELECT group_concat(i.notes, ' ') FROM items i
+     WHERE substr(i.file_path, 1, length(files.path) + 1) IN (files.path || '/', files.path || '\'))
+"#;
+
+/// Como [`reindex_files`], con [`RELATIVE_FILES_FTS_COLUMNS`]
+fn reindex_relative_files(rowids: &str) -> String {
+    format!(
+        "DELETE FROM files_fts WHERE rowid IN ({rowids});
+         INSERT INTO files_fts (rowid, name, tags, category, notes) SELECT {RELATIVE_FILES_FTS_COLUMNS} FROM files WHERE rowid IN ({rowids});"
+    )
+}
+
+/// Triggers de `files_fts` desde el paso 7 -> 8; reemplazan a los que hubiera. Los usan pasos ya
+/// publicados, así que tampoco se modifican.
+fn relative_files_fts_triggers() -> String {
+    let item_folder = |item: &str| {
+        format!(
+            "SELECT rowid FROM files WHERE substr({item}.file_path, 1, length(path) + 1) IN (path || '/', path || '\\')"
+        )
+    };
+    let category_folders = "SELECT rowid FROM files WHERE instr('/' || replace(path, '\\', '/'), '/' || NEW.name || '/') > 0";
+    let tagged_folders = "SELECT f.rowid FROM files f JOIN file_tags ft ON ft.file_path = f.path WHERE ft.tag_id = NEW.id";
+
+    format!(
+        r#"
+        DROP TRIGGER IF EXISTS files_fts_insert;
+        CREATE TRIGGER files_fts_insert AFTER INSERT ON files BEGIN
+            {new_file}
+        END;
+        DROP TRIGGER IF EXISTS files_fts_update;
+        CREATE TRIGGER files_fts_update AFTER UPDATE OF path, name ON files BEGIN
+            {new_file}
+        END;
+        DROP TRIGGER IF EXISTS files_fts_delete;
+        CREATE TRIGGER files_fts_delete AFTER DELETE ON files BEGIN
+            DELETE FROM files_fts WHERE rowid = OLD.rowid;
+        END;
+
+        DROP TRIGGER IF EXISTS file_tags_fts_insert;
+        CREATE TRIGGER file_tags_fts_insert AFTER INSERT ON file_tags BEGIN
+            {new_file_tag}
+        END;
+        DROP TRIGGER IF EXISTS file_tags_fts_update;
+        CREATE TRIGGER file_tags_fts_update AFTER UPDATE ON file_tags BEGIN
+            {new_file_tag}
+        END;
+        DROP TRIGGER IF EXISTS file_tags_fts_delete;
+        CREATE TRIGGER file_tags_fts_delete AFTER DELETE ON file_tags BEGIN
+            {old_file_tag}
+        END;
+        DROP TRIGGER IF EXISTS tags_fts_update;
+        CREATE TRIGGER tags_fts_update AFTER UPDATE OF name ON tags BEGIN
+            {tag}
+        END;
+
+        DROP TRIGGER IF EXISTS items_fts_insert;
+        CREATE TRIGGER items_fts_insert AFTER INSERT ON items BEGIN
+            {new_item}
+        END;
+        DROP TRIGGER IF EXISTS items_fts_update;
+        CREATE TRIGGER items_fts_update AFTER UPDATE OF file_path, notes ON items BEGIN
+            {old_item}
+            {new_item}
+        END;
+        DROP TRIGGER IF EXISTS items_fts_delete;
+        CREATE TRIGGER items_fts_delete AFTER DELETE ON items BEGIN
+            {old_item}
+        END;
+
+        DROP TRIGGER IF EXISTS categories_fts_insert;
+        CREATE TRIGGER categories_fts_insert AFTER INSERT ON categories BEGIN
+            {category}
+        END;
+        DROP TRIGGER IF EXISTS categories_fts_update;
+        CREATE TRIGGER categories_fts_update AFTER UPDATE OF parent_id, name ON categories BEGIN
+            {category}
+        END;
+        "#,
+        new_file = reindex_relative_files("NEW.rowid"),
+        new_file_tag = reindex_relative_files("SELECT rowid FROM files WHERE path = NEW.file_path"),
+        old_file_tag = reindex_relative_files("SELECT rowid FROM files WHERE path = OLD.file_path"),
+        tag = reindex_relative_files(tagged_folders),
+        new_item = reindex_relative_files(&item_folder("NEW")),
+        old_item = reindex_relative_files(&item_folder("OLD")),
+        category = reindex_relative_files(category_folders),
+    )
+}
+
+/// 7 -> 8: la base queda ligada a la carpeta principal (`library`) y las rutas de dentro se guardan
+/// relativas a ella y con `/`, para poder mover la biblioteca. Con las claves foráneas desactivadas
+/// no hay cascada, así que se reescriben todas las columnas; los triggers de `files_fts` se
+/// recrean porque ahora tienen que entender rutas relativas.
+fn relative_paths(tx: &Transaction, root: &Path) -> StoreResult<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE library (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            root TEXT NOT NULL
+        );
+        "#,
+    )?;
+
+    let root = root.display().to_string();
+    let root = root.trim_end_matches(['/', '\\']);
+    tx.execute("INSERT INTO library (id, root) VALUES (1, ?1)", params![root])?;
+    for (table, column) in [
+        ("files", "path"),
+        ("file_tags", "file_path"),
+        ("package_assets", "item_path"),
+        ("package_assets", "package"),
+        ("items", "file_path"),
+        ("items", "image_path"),
+        ("item_files", "path"),
+        ("file_hashes", "path"),
+        ("file_hashes", "item_path"),
+        ("file_links", "path"),
+        ("file_links", "item_path"),
+        ("file_links", "target"),
+        ("item_versions", "item_path"),
+        ("item_versions", "file_path"),
+    ] {
+        tx.execute(
+            &format!(
+                "UPDATE {table} SET {column} = replace(substr({column}, length(?1) + 2), ?2, '/')
+                 WHERE substr({column}, 1, length(?1) + 1) = ?1 || ?2"
+            ),
+            params![root, std::path::MAIN_SEPARATOR_STR],
+        )?;
+    }
+
+    let sql = format!(
+        r#"
+        {triggers}
+
+        DELETE FROM files_fts;
+        INSERT INTO files_fts (rowid, name, tags, category, notes) SELECT {RELATIVE_FILES_FTS_COLUMNS} FROM files;
+        "#,
+        triggers = relative_files_fts_triggers(),
+    );
+    tx.execute_batch(&sql)?;
+    Ok(())
+}
+
+/// 8 -> 9: `items.subcategory` quedó vacía desde que la categoría es la ruta completa (paso 0 -> 1)
+fn drop_item_subcategory(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    tx.execute_batch("ALTER TABLE items DROP COLUMN subcategory;")?;
+    Ok(())
+}
+
+/// 9 -> 10: borrar una categoría (o que su carpeta desaparezca fuera de la aplicación) ya no
+/// borra sus items; se quedan sin `category_id` hasta que la carpeta vuelva a aparecer.
+/// SQLite no cambia una clave foránea en sitio, así que se rehace la tabla; los triggers que la
+/// nombran se quitan antes y se vuelven a crear tal cual.
+fn keep_orphan_items(tx: &Transaction, _root: &Path) -> StoreResult<()> {
+    let triggers = {
+        let mut stmt = tx.prepare("SELECT name, sql FROM sqlite_master WHERE type = 'trigger'")?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+    for (name, _) in &triggers {
+        tx.execute_batch(&format!("DROP TRIGGER \"{name}\";"))?;
+    }
+
+    tx.execute_batch(
+        r#"
+        CREATE TABLE items_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            category TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            image_path TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL,
+            notes TEXT
+        );
+        INSERT INTO items_new (id, name, category, file_path, image_path, created_at, updated_at, category_id, notes)
+            SELECT id, name, category, file_path, image_path, created_at, updated_at, category_id, notes FROM items;
+        DROP TABLE items;
+        ALTER TABLE items_new RENAME TO items;
+        "#,
+    )?;
+    for (_, sql) in &triggers {
+        tx.execute_batch(&format!("{sql};"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use std::fs;
+
+    /// Base de las versiones sin `user_version`: categorías planas con subcategorías aparte, tags
+    /// de las carpetas en JSON y rutas absolutas
+    fn legacy_fixture(conn: &Connection, root: &Path) {
+        let path = |relative: &str| relative.split('/').fold(root.to_path_buf(), |dir, name| dir.join(name)).display().to_string();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE files (path TEXT PRIMARY KEY, name TEXT NOT NULL, tags TEXT NOT NULL DEFAULT '[]');
+            CREATE TABLE package_assets (
+                item_path TEXT NOT NULL, package TEXT NOT NULL, guid TEXT NOT NULL, asset_path TEXT NOT NULL,
+                size INTEGER NOT NULL DEFAULT 0, PRIMARY KEY (package, guid)
+            );
+            CREATE TABLE tags (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL);
+            CREATE TABLE categories (name TEXT NOT NULL);
+            CREATE TABLE subcategories (category TEXT NOT NULL, name TEXT NOT NULL);
+            CREATE TABLE items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, category TEXT NOT NULL, subcategory TEXT,
+                file_path TEXT NOT NULL, image_path TEXT,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP, updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE item_tags (item_id INTEGER NOT NULL, tag_id INTEGER NOT NULL, PRIMARY KEY (item_id, tag_id));
+            CREATE TABLE item_files (id INTEGER PRIMARY KEY AUTOINCREMENT, item_id INTEGER NOT NULL, path TEXT NOT NULL, role TEXT NOT NULL);
+
+            INSERT INTO categories (name) VALUES ('Props'), ('Avatars');
+            INSERT INTO subcategories (category, name) VALUES ('Avatars', 'Manuka'), ('Nada', 'Huérfana');
+            INSERT INTO tags (name) VALUES ('toon');
+            "#,
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO files (path, name, tags) VALUES (?1, 'Outfit', '[\"toon\",\"nsfw\"]'), (?2, 'Chair', 'no es json'), ('/fuera/Thing', 'Thing', '[\"toon\"]')",
+            params![path("Avatars/Manuka/Outfit"), path("Props/Chair")],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO package_assets (item_path, package, guid, asset_path, size) VALUES (?1, ?2, 'g1', 'Assets/Outfit/Outfit.prefab', 10)",
+            params![path("Avatars/Manuka/Outfit"), path("Avatars/Manuka/Outfit/Outfit.unitypackage")],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO items (id, name, category, subcategory, file_path, image_path) VALUES (1, 'Outfit', 'Avatars', 'Manuka', ?1, ?2)",
+            params![path("Avatars/Manuka/Outfit/Outfit.unitypackage"), path("Avatars/Manuka/Outfit/preview.png")],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO item_files (item_id, path, role) VALUES (1, ?1, 'package'), (7, '/fuera/x.zip', 'package')",
+            params![path("Avatars/Manuka/Outfit/Outfit.unitypackage")],
+        )
+        .unwrap();
+        conn.execute_batch("INSERT INTO item_tags (item_id, tag_id) VALUES (1, 1), (1, 99);").unwrap();
+    }
+
+    /// Lleva la base a `version` con los mismos pasos que [`run`]
+    fn migrate_to(db_path: &str, root: &Path, version: usize) {
+        let mut conn = Connection::open(db_path).unwrap();
+        for (i, step) in MIGRATIONS.iter().enumerate().take(version) {
+            let tx = conn.transaction().unwrap();
+            step(&tx, root).unwrap();
+            tx.pragma_update(None, "user_version", (i + 1) as i64).unwrap();
+            tx.commit().unwrap();
+        }
+    }
+
+    fn strings(conn: &Connection, sql: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(sql).unwrap();
+        stmt.query_map([], |r| r.get(0)).unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    /// Parte de la base antigua llevada a `version` y comprueba que [`run`] la deja al día
+    fn migrates_from(version: usize) {
+        let root = std::env::temp_dir().join(format!("vrcfm-migrations-{}-v{}", std::process::id(), version));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let db_path = root.join("files.db").display().to_string();
+
+        legacy_fixture(&Connection::open(&db_path).unwrap(), &root);
+        migrate_to(&db_path, &root, version);
+
+        let backup = run(&db_path, &root).unwrap().expect("copia de seguridad");
+        assert!(backup.ends_with(format!("files.db.v{}.bak", version)));
+        assert!(backup.is_file());
+
+        let db = Database::open(&db_path).unwrap();
+        let conn = Connection::open(&db_path).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+        assert_eq!(db.root(), Some(root.as_path()));
+
+        // Categorías y subcategorías forman un solo árbol
+        let categories = db.categories().get_categories().unwrap();
+        let paths: Vec<&str> = categories.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, ["Avatars", "Avatars/Manuka", "Props"]);
+        let manuka = categories[1].id;
+
+        // Los tags en JSON pasan a `file_tags`, creando los que faltaban
+        assert_eq!(
+            strings(&conn, "SELECT ft.file_path || ':' || t.name FROM file_tags ft JOIN tags t ON t.id = ft.tag_id ORDER BY 1"),
+            ["/fuera/Thing:toon", "Avatars/Manuka/Outfit:nsfw", "Avatars/Manuka/Outfit:toon"]
+        );
+        assert!(!column_exists(&conn, "files", "tags").unwrap());
+
+        // Las rutas de dentro de la biblioteca quedan relativas; las de fuera, tal cual
+        assert_eq!(strings(&conn, "SELECT path FROM files ORDER BY path"), ["/fuera/Thing", "Avatars/Manuka/Outfit", "Props/Chair"]);
+        assert_eq!(
+            strings(&conn, "SELECT item_path || ' ' || package FROM package_assets"),
+            ["Avatars/Manuka/Outfit Avatars/Manuka/Outfit/Outfit.unitypackage"]
+        );
+        assert_eq!(strings(&conn, "SELECT path FROM item_files"), ["Avatars/Manuka/Outfit/Outfit.unitypackage"]);
+        assert_eq!(strings(&conn, "SELECT CAST(item_id AS TEXT) || ':' || CAST(tag_id AS TEXT) FROM item_tags"), ["1:1"]);
+
+        // El item lleva la ruta completa de su categoría y ya no tiene subcategoría
+        let item = db.items().get_item(1).unwrap().unwrap();
+        assert_eq!(item.category, "Avatars/Manuka");
+        assert_eq!(item.file_path, root.join("Avatars").join("Manuka").join("Outfit").join("Outfit.unitypackage").display().to_string());
+        assert_eq!(item.image_path, Some(root.join("Avatars").join("Manuka").join("Outfit").join("preview.png").display().to_string()));
+        assert!(!column_exists(&conn, "items", "subcategory").unwrap());
+        let category_id: Option<i64> = conn.query_row("SELECT category_id FROM items WHERE id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(category_id, Some(manuka));
+
+        // El índice de texto completo encuentra por tag y por categoría con las rutas relativas
+        let fts = |text: &str| {
+            strings(&conn, &format!("SELECT f.path FROM files_fts JOIN files f ON f.rowid = files_fts.rowid WHERE files_fts MATCH '{text}' ORDER BY 1"))
+        };
+        assert_eq!(fts("nsfw"), ["Avatars/Manuka/Outfit"]);
+        assert_eq!(fts("category:\"Avatars/Manuka\""), ["Avatars/Manuka/Outfit"]);
+        assert_eq!(fts("category:Props"), ["Props/Chair"]);
+
+        // Los triggers siguen al día tras rehacer `items`
+        db.files().set_file_tags(&root.join("Props").join("Chair").display().to_string(), &["madera".to_string()]).unwrap();
+        assert_eq!(fts("madera"), ["Props/Chair"]);
+
+        // Borrar la categoría deja el item sin ella
+        db.categories().delete_category(manuka).unwrap();
+        let category_id: Option<i64> = conn.query_row("SELECT category_id FROM items WHERE id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(category_id, None);
+
+        drop((db, conn));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    macro_rules! migrates_from {
+        ($($name:ident: $version:expr,)*) => {
+            $(
+                #[test]
+                fn $name() {
+                    migrates_from($version);
+                }
+            )*
+
+            #[test]
+            fn every_version_is_tested() {
+                assert_eq!([$($version),*], std::array::from_fn::<usize, { LATEST_VERSION as usize }, _>(|i| i));
+            }
+        };
+    }
+
+    migrates_from! {
+        from_v0: 0,
+        from_v1: 1,
+        from_v2: 2,
+        from_v3: 3,
+        from_v4: 4,
+        from_v5: 5,
+        from_v6: 6,
+        from_v7: 7,
+        from_v8: 8,
+        from_v9: 9,
+    }
+
+    #[test]
+    fn newer_database_is_rejected() {
+        let root = std::env::temp_dir().join(format!("vrcfm-migrations-{}-newer", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let db_path = root.join("files.db").display().to_string();
+        Connection::open(&db_path).unwrap().pragma_update(None, "user_version", LATEST_VERSION + 1).unwrap();
+
+        assert!(matches!(run(&db_path, &root), Err(StoreError::Migration(_))));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,8 +1,199 @@
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IndexedFile {
     pub path: String,
     pub name: String,
     pub tags: Vec<String>,
+    /// Tamaño total en bytes
+    #[serde(default)]
+    pub size: u64,
+    /// Última modificación (segundos desde UNIX_EPOCH)
+    #[serde(default)]
+    pub mtime: i64,
+    /// Hash BLAKE3 del contenido, si se calculó
+    #[serde(default)]
+    pub hash: Option<String>,
+    /// Versión actual de `item_versions`; solo se lee, `insert_file` la ignora
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// Carpeta encontrada por una búsqueda
+#[derive(Debug, Clone, Default)]
+pub struct SearchHit {
+    pub file: IndexedFile,
+    /// Fragmento donde coincidió la búsqueda, con las coincidencias entre
+    /// [`MATCH_START`](crate::sqlite_store::MATCH_START) y [`MATCH_END`](crate::sqlite_store::MATCH_END)
+    pub snippet: Option<String>,
+}
+
+/// Item guardado desde el Item Manager
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Item {
+    pub id: i64,
+    pub name: String,
+    pub category: String,
+    pub file_path: String,
+    pub image_path: Option<String>,
+    pub tags: Vec<String>,
+    /// Notas libres; entran en la búsqueda
+    #[serde(default)]
+    pub notes: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Asset contenido en un `.unitypackage`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageAsset {
+    /// Ruta del `.unitypackage` que lo contiene
+    pub package: String,
+    pub guid: String,
+    /// Ruta dentro del proyecto de Unity, p. ej. `Assets/Foo/Shaders/Toon.shader`
+    pub asset_path: String,
+    pub size: u64,
+}
+
+/// Papel de un archivo guardado en la carpeta de un item
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileRole {
+    #[default]
+    Package,
+    Image,
+    Readme,
+    License,
+}
+
+impl FileRole {
+    pub const ALL: [FileRole; 4] = [FileRole::Package, FileRole::Image, FileRole::Readme, FileRole::License];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileRole::Package => "package",
+            FileRole::Image => "image",
+            FileRole::Readme => "readme",
+            FileRole::License => "license",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        Self::ALL.into_iter().find(|r| r.as_str() == s).unwrap_or_default()
+    }
+
+    /// Deduce el papel por el nombre del archivo; lo que no es imagen, readme ni licencia es un paquete
+    pub fn detect(path: &std::path::Path) -> Self {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+
+        if stem.starts_with("readme") {
+            FileRole::Readme
+        } else if stem.starts_with("license") || stem.starts_with("licence") {
+            FileRole::License
+        } else if matches!(ext.as_str(), "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp") {
+            FileRole::Image
+        } else {
+            FileRole::Package
+        }
+    }
+}
+
+/// Nodo del árbol de categorías; cada una es una carpeta dentro de la de su padre
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Category {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    pub name: String,
+    pub sort_order: i64,
+    /// Ruta desde la raíz, p. ej. `Avatars/Base Models/Manuka`
+    pub path: String,
+}
+
+impl Category {
+    /// 0 para las categorías raíz
+    pub fn depth(&self) -> usize {
+        self.path.matches('/').count()
+    }
+}
+
+/// Hash del contenido de un archivo dentro de una carpeta de item
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileHash {
+    pub path: String,
+    /// Carpeta de item (fila de `files`) que lo contiene
+    pub item_path: String,
+    pub size: u64,
+    pub mtime: i64,
+    /// BLAKE3 del contenido
+    pub hash: String,
+}
+
+/// Archivos con el mismo contenido
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub hash: String,
+    /// Tamaño de cada copia
+    pub size: u64,
+    pub paths: Vec<String>,
+}
+
+impl DuplicateGroup {
+    /// Espacio que se libera dejando una sola copia
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// Copia duplicada que se eliminó; la base apunta a `target` en su lugar
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileLink {
+    pub path: String,
+    pub target: String,
+    /// Segundos desde UNIX_EPOCH
+    pub created_at: i64,
+}
+
+/// Búsqueda guardada con nombre; `query` es el texto del buscador (ver [`crate::query::Query`])
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+}
+
+/// Versión de un item: uno de los paquetes de su carpeta, con la versión sacada del nombre
+/// (ver [`crate::versions::detect`])
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemVersion {
+    pub id: i64,
+    /// Carpeta de item (fila de `files`)
+    pub item_path: String,
+    pub file_path: String,
+    pub version: String,
+    /// Segundos desde UNIX_EPOCH
+    pub imported_at: i64,
+    /// Notas de la versión
+    pub changelog: Option<String>,
+    /// La más nueva del item
+    pub current: bool,
+}
+
+/// Datos de una carpeta de item que se guardan junto a ella en la papelera para recuperarlos al
+/// restaurarla. Las rutas son relativas a la carpeta enviada a la papelera.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedFolder {
+    pub file: IndexedFile,
+    #[serde(default)]
+    pub versions: Vec<ItemVersion>,
+    #[serde(default)]
+    pub assets: Vec<PackageAsset>,
+}
+
+/// Archivo guardado en la carpeta de un item
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ItemFile {
+    pub id: i64,
+    pub item_id: i64,
+    pub path: String,
+    pub role: FileRole,
 }
\ No newline at end of file
@@ -0,0 +1,173 @@
+use eframe::egui;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Tiempo que un aviso queda visible en pantalla
+const TOAST_DURATION: Duration = Duration::from_secs(6);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn icon(&self) -> &'static str {
+        match self {
+            Level::Info => "ℹ",
+            Level::Warning => "⚠",
+            Level::Error => "❌",
+        }
+    }
+
+    fn color(&self, visuals: &egui::Visuals) -> egui::Color32 {
+        match self {
+            Level::Info => visuals.text_color(),
+            Level::Warning => visuals.warn_fg_color,
+            Level::Error => visuals.error_fg_color,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub level: Level,
+    pub message: String,
+    /// Archivo o carpeta relacionado, para poder abrirlo desde el log
+    pub path: Option<PathBuf>,
+}
+
+/// Avisos temporales en pantalla más un historial que se guarda en `log_file`
+pub struct Notifications {
+    log: Vec<LogEntry>,
+    toasts: Vec<(LogEntry, Instant)>,
+    log_file: PathBuf,
+}
+
+impl Notifications {
+    pub fn new(log_file: impl Into<PathBuf>) -> Self {
+        Self { log: Vec::new(), toasts: Vec::new(), log_file: log_file.into() }
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Level::Info, message.into(), None);
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.push(Level::Warning, message.into(), None);
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Level::Error, message.into(), None);
+    }
+
+    /// Error relacionado con un archivo o carpeta
+    pub fn error_at(&mut self, message: impl Into<String>, path: impl Into<PathBuf>) {
+        self.push(Level::Error, message.into(), Some(path.into()));
+    }
+
+    /// Registra el error de `result` (si lo hay) con `context` delante
+    pub fn check<T, E: std::fmt::Display>(&mut self, context: &str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(v) => Some(v),
+            Err(e) => {
+                self.error(format!("{}: {}", context, e));
+                None
+            }
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.log.iter().filter(|e| e.level == Level::Error).count()
+    }
+
+    pub fn clear(&mut self) {
+        self.log.clear();
+    }
+
+    fn push(&mut self, level: Level, message: String, path: Option<PathBuf>) {
+        let entry = LogEntry {
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            level,
+            message,
+            path,
+        };
+
+        // El log en disco es best-effort; si falla no hay dónde avisarlo
+        if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(&self.log_file) {
+            let _ = writeln!(f, "{} [{:?}] {}", entry.time, entry.level, entry.message);
+        }
+
+        self.toasts.push((entry.clone(), Instant::now()));
+        self.log.push(entry);
+    }
+
+    /// Dibuja los avisos activos en la esquina inferior derecha
+    pub fn show_toasts(&mut self, ctx: &egui::Context) {
+        self.toasts.retain(|(_, shown)| shown.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                let mut dismissed = None;
+                for (i, (entry, _)) in self.toasts.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.set_max_width(360.0);
+                        ui.horizontal(|ui| {
+                            let color = entry.level.color(ui.visuals());
+                            ui.colored_label(color, entry.level.icon());
+                            ui.label(&entry.message);
+                            if ui.small_button("✖").clicked() {
+                                dismissed = Some(i);
+                            }
+                        });
+                    });
+                }
+                if let Some(i) = dismissed {
+                    self.toasts.remove(i);
+                }
+            });
+
+        // Volver a dibujar cuando caduque el siguiente aviso
+        ctx.request_repaint_after(Duration::from_millis(500));
+    }
+
+    /// Panel inferior con el historial completo
+    pub fn show_log(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("log_panel")
+            .resizable(true)
+            .default_height(160.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Log");
+                    if ui.button("🗑 Clear").clicked() {
+                        self.clear();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().stick_to_bottom(true).auto_shrink(false).show(ui, |ui| {
+                    for entry in &self.log {
+                        ui.horizontal(|ui| {
+                            ui.weak(&entry.time);
+                            ui.colored_label(entry.level.color(ui.visuals()), entry.level.icon());
+                            ui.label(&entry.message);
+                            if let Some(path) = &entry.path
+                                && ui.small_button("📂 Abrir").on_hover_text(path.display().to_string()).clicked()
+                            {
+                                let target = if path.is_dir() { path.as_path() } else { path.parent().unwrap_or(path) };
+                                let _ = open::that(target);
+                            }
+                        });
+                    }
+                });
+            });
+    }
+}
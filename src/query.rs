@@ -0,0 +1,454 @@
+use chrono::{NaiveDate, Utc};
+use rusqlite::types::Value;
+use std::fmt;
+
+/// Segundos de cada unidad que acepta `added:`
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+
+/// Búsqueda escrita en el buscador, p. ej.
+/// `tag:toon -tag:nsfw category:Avatars/Manuka size:>50MB added:<30d ext:unitypackage "frase exacta"`.
+///
+/// Las palabras sueltas se buscan por prefijo en el índice de texto completo, las frases entre
+/// comillas tal cual, y un `-` delante de cualquier término lo excluye. Todos los términos tienen que
+/// cumplirse a la vez.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub negated: bool,
+    pub term: Term,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// Palabra suelta; coincide por prefijo
+    Word(String),
+    /// `"frase exacta"`
+    Phrase(String),
+    /// `tag:nombre`
+    Tag(String),
+    /// `category:Avatars/Manuka`; incluye las subcategorías
+    Category(String),
+    /// `size:>50MB`, en bytes
+    Size(Cmp, u64),
+    /// `added:<30d` o `added:>=2024-01-01`, como rango `[desde, hasta)` en segundos desde UNIX_EPOCH
+    Added(Option<i64>, Option<i64>),
+    /// `ext:unitypackage`, sin el punto y en minúsculas
+    Ext(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl Cmp {
+    fn sql(self) -> &'static str {
+        match self {
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+            Cmp::Eq => "=",
+        }
+    }
+
+    /// Separa el operador del principio de `value`; sin operador es `=`
+    fn split(value: &str) -> (Cmp, &str) {
+        for (prefix, cmp) in [("<=", Cmp::Le), (">=", Cmp::Ge), ("<", Cmp::Lt), (">", Cmp::Gt), ("=", Cmp::Eq)] {
+            if let Some(rest) = value.strip_prefix(prefix) {
+                return (cmp, rest);
+            }
+        }
+        (Cmp::Eq, value)
+    }
+}
+
+/// Error al interpretar la búsqueda; `position` es el carácter donde empieza el término culpable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Carácter {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Búsqueda lista para `SqliteStore`: la parte de texto completo y las condiciones sobre `files`
+#[derive(Debug, Clone, Default)]
+pub struct CompiledQuery {
+    /// Consulta FTS5 con las palabras y frases que tienen que aparecer; `None` si no hay ninguna
+    pub fts: Option<String>,
+    /// Condiciones sobre la tabla `files` unidas con AND (`1` si no hay)
+    pub filter: String,
+    /// Valores de los parámetros `:p0`, `:p1`... de `filter`
+    pub params: Vec<(String, Value)>,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, ParseError> {
+        let mut clauses = Vec::new();
+        let mut chars = input.char_indices().peekable();
+
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            let position = input[..start].chars().count();
+            let error = |message: String| ParseError { position, message };
+
+            let negated = c == '-';
+            if negated {
+                chars.next();
+            }
+
+            // El término llega hasta el siguiente espacio que no esté entre comillas
+            let mut raw = String::new();
+            let mut in_quotes = false;
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() && !in_quotes {
+                    break;
+                }
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                raw.push(c);
+                chars.next();
+            }
+            if in_quotes {
+                return Err(error("faltan las comillas de cierre".into()));
+            }
+            if raw.is_empty() {
+                return Err(error("falta el término después de '-'".into()));
+            }
+
+            let term = match raw.split_once(':') {
+                Some((key, value)) if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()) => {
+                    let value = unquote(value);
+                    if value.is_empty() {
+                        return Err(error(format!("falta el valor de '{}:'", key)));
+                    }
+                    parse_field(key, &value).map_err(error)?
+                }
+                _ if raw.starts_with('"') => {
+                    let phrase = unquote(&raw);
+                    if phrase.trim().is_empty() {
+                        return Err(error("la frase está vacía".into()));
+                    }
+                    Term::Phrase(phrase)
+                }
+                // Las palabras ya son prefijos; un `*` final sobra, y uno suelto no busca nada
+                _ => match unquote(&raw).trim_end_matches('*') {
+                    "" => continue,
+                    word => Term::Word(word.to_string()),
+                },
+            };
+            clauses.push(Clause { negated, term });
+        }
+
+        Ok(Query { clauses })
+    }
+
+    /// Traduce la búsqueda a SQL; los valores nunca se pegan en el texto, van como parámetros
+    pub fn compile(&self) -> CompiledQuery {
+        let mut fts = Vec::new();
+        let mut conditions = Vec::new();
+        let mut params: Vec<(String, Value)> = Vec::new();
+        let mut param = |value: Value| {
+            let name = format!(":p{}", params.len());
+            params.push((name.clone(), value));
+            name
+        };
+
+        for clause in &self.clauses {
+            let condition = match &clause.term {
+                Term::Word(_) | Term::Phrase(_) => {
+                    let term = clause.term.fts();
+                    if !clause.negated {
+                        fts.push(term);
+                        continue;
+                    }
+                    let p = param(Value::Text(term));
+                    format!("files.rowid IN (SELECT rowid FROM files_fts WHERE files_fts MATCH {p})")
+                }
+                Term::Tag(tag) => {
+                    let p = param(Value::Text(tag.clone()));
+                    format!(
+                        "EXISTS (SELECT 1 FROM file_tags ft JOIN tags t ON t.id = ft.tag_id
+                                 WHERE ft.file_path = files.path AND t.name = {p} COLLATE NOCASE)"
+                    )
+                }
+                Term::Category(path) => {
+                    let p = param(Value::Text(path.to_lowercase()));
+                    format!(
+                        "files.rowid IN (SELECT rowid FROM files_fts
+                                         WHERE lower(category) = {p} OR substr(lower(category), 1, length({p}) + 1) = {p} || '/')"
+                    )
+                }
+                Term::Size(cmp, bytes) => {
+                    let p = param(Value::Integer(*bytes as i64));
+                    format!("files.size {} {p}", cmp.sql())
+                }
+                Term::Added(from, to) => {
+                    let mut range = Vec::new();
+                    if let Some(from) = from {
+                        range.push(format!("files.added_at >= {}", param(Value::Integer(*from))));
+                    }
+                    if let Some(to) = to {
+                        range.push(format!("files.added_at < {}", param(Value::Integer(*to))));
+                    }
+                    range.join(" AND ")
+                }
+                Term::Ext(ext) => {
+                    let p = param(Value::Text(ext.clone()));
+                    // Los archivos de la carpeta solo se conocen por los paquetes indexados y los
+                    // archivos guardados desde el Item Manager
+                    format!(
+                        "(EXISTS (SELECT 1 FROM package_assets a WHERE a.item_path = files.path
+                                  AND (lower(substr(a.package, -length({p}) - 1)) = '.' || {p}
+                                       OR lower(substr(a.asset_path, -length({p}) - 1)) = '.' || {p}))
+                          OR EXISTS (SELECT 1 FROM item_files f WHERE substr(f.path, 1, length(files.path) + 1) IN (files.path || '/', files.path || '\\')
+                                     AND lower(substr(f.path, -length({p}) - 1)) = '.' || {p}))"
+                    )
+                }
+            };
+            conditions.push(if clause.negated { format!("NOT ({})", condition) } else { condition });
+        }
+
+        CompiledQuery {
+            fts: if fts.is_empty() { None } else { Some(fts.join(" ")) },
+            filter: if conditions.is_empty() { "1".into() } else { conditions.join("\n AND ") },
+            params,
+        }
+    }
+}
+
+impl Term {
+    /// Término en sintaxis FTS5; solo tiene sentido para las palabras y frases
+    fn fts(&self) -> String {
+        match self {
+            Term::Word(word) => format!("{}*", fts_string(word)),
+            Term::Phrase(phrase) => fts_string(phrase),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Cadena FTS5 entre comillas, así los operadores que escriba el usuario no se interpretan
+fn fts_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn unquote(value: &str) -> String {
+    value.replace('"', "")
+}
+
+fn parse_field(key: &str, value: &str) -> Result<Term, String> {
+    match key.to_ascii_lowercase().as_str() {
+        "tag" => Ok(Term::Tag(value.to_string())),
+        "category" => Ok(Term::Category(value.trim_matches('/').to_string())),
+        "size" => {
+            let (cmp, size) = Cmp::split(value);
+            let bytes = parse_size(size).ok_or_else(|| format!("tamaño no válido '{}' (p. ej. size:>50MB)", value))?;
+            Ok(Term::Size(cmp, bytes))
+        }
+        "added" => {
+            let (cmp, when) = Cmp::split(value);
+            parse_added(cmp, when)
+                .ok_or_else(|| format!("fecha no válida '{}' (p. ej. added:<30d o added:>2024-01-31)", value))
+        }
+        "ext" => Ok(Term::Ext(value.trim_start_matches('.').to_lowercase())),
+        _ => Err(format!("campo desconocido '{}' (se puede usar tag, category, size, added y ext)", key)),
+    }
+}
+
+/// `50MB`, `1.5gb`, `300` (bytes); múltiplos de 1024 como en el resto de la aplicación. Los
+/// tamaños que no caben en un entero de SQLite no son válidos.
+fn parse_size(text: &str) -> Option<u64> {
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1u64,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return None,
+    };
+    let bytes = number * multiplier as f64;
+    (bytes.is_finite() && bytes < i64::MAX as f64).then_some(bytes as u64)
+}
+
+/// Una antigüedad (`30d`, `2w`, `6m`, `1y`, `12h`) o un día (`2024-01-31`) como rango de fechas
+fn parse_added(cmp: Cmp, text: &str) -> Option<Term> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let day = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+        let next = day + DAY;
+        let (from, to) = match cmp {
+            Cmp::Lt => (None, Some(day)),
+            Cmp::Le => (None, Some(next)),
+            Cmp::Gt => (Some(next), None),
+            Cmp::Ge => (Some(day), None),
+            Cmp::Eq => (Some(day), Some(next)),
+        };
+        return Some(Term::Added(from, to));
+    }
+
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number.parse().ok()?;
+    let unit = match unit.to_ascii_lowercase().as_str() {
+        "h" => HOUR,
+        "d" | "" => DAY,
+        "w" => 7 * DAY,
+        "m" => 30 * DAY,
+        "y" => 365 * DAY,
+        _ => return None,
+    };
+    // `<30d` es "hace menos de 30 días": añadido después de ese momento
+    let moment = Utc::now().timestamp().checked_sub(number.checked_mul(unit)?)?;
+    let (from, to) = match cmp {
+        Cmp::Lt | Cmp::Le | Cmp::Eq => (Some(moment), None),
+        Cmp::Gt | Cmp::Ge => (None, Some(moment)),
+    };
+    Some(Term::Added(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(input: &str) -> Vec<(bool, Term)> {
+        Query::parse(input).unwrap().clauses.into_iter().map(|c| (c.negated, c.term)).collect()
+    }
+
+    fn error_at(input: &str) -> usize {
+        Query::parse(input).unwrap_err().position
+    }
+
+    #[test]
+    fn words_phrases_and_fields() {
+        assert_eq!(
+            terms(r#"manuka "pelo largo" tag:toon category:/Avatars/Manuka/ ext:.UnityPackage"#),
+            vec![
+                (false, Term::Word("manuka".into())),
+                (false, Term::Phrase("pelo largo".into())),
+                (false, Term::Tag("toon".into())),
+                (false, Term::Category("Avatars/Manuka".into())),
+                (false, Term::Ext("unitypackage".into())),
+            ]
+        );
+        assert_eq!(terms(r#"tag:"dos palabras""#), vec![(false, Term::Tag("dos palabras".into()))]);
+        // Lo que no parece un campo es una palabra
+        assert_eq!(terms("v1:2"), vec![(false, Term::Word("v1:2".into()))]);
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(
+            terms(r#"-tag:nsfw -old -"no esto""#),
+            vec![
+                (true, Term::Tag("nsfw".into())),
+                (true, Term::Word("old".into())),
+                (true, Term::Phrase("no esto".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn bare_wildcards_are_ignored() {
+        assert_eq!(terms("* ** toon*"), vec![(false, Term::Word("toon".into()))]);
+        assert_eq!(Query::parse("*").unwrap().compile().fts, None);
+    }
+
+    #[test]
+    fn size_units() {
+        assert_eq!(terms("size:300"), vec![(false, Term::Size(Cmp::Eq, 300))]);
+        assert_eq!(terms("size:>50MB"), vec![(false, Term::Size(Cmp::Gt, 50 << 20))]);
+        assert_eq!(terms("size:<=1.5gb"), vec![(false, Term::Size(Cmp::Le, 3 << 29))]);
+        assert_eq!(terms("size:>=2k"), vec![(false, Term::Size(Cmp::Ge, 2048))]);
+        assert!(Query::parse("size:>50XB").is_err());
+        assert!(Query::parse("size:MB").is_err());
+    }
+
+    #[test]
+    fn huge_sizes_are_errors() {
+        assert_eq!(error_at("toon size:>99999999999TB"), 5);
+        assert_eq!(error_at("size:<99999999999TB"), 0);
+        assert!(Query::parse(&format!("size:>{}", "9".repeat(400))).is_err());
+        assert_eq!(terms("size:<8000000TB"), vec![(false, Term::Size(Cmp::Lt, 8_000_000 << 40))]);
+    }
+
+    #[test]
+    fn dates() {
+        let day = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+        assert_eq!(terms("added:2024-01-31"), vec![(false, Term::Added(Some(day), Some(day + DAY)))]);
+        assert_eq!(terms("added:<2024-01-31"), vec![(false, Term::Added(None, Some(day)))]);
+        assert_eq!(terms("added:>2024-01-31"), vec![(false, Term::Added(Some(day + DAY), None))]);
+
+        let now = Utc::now().timestamp();
+        let [(_, Term::Added(Some(from), None))] = terms("added:<30d")[..] else {
+            panic!("added:<30d");
+        };
+        assert!((now - 30 * DAY - from).abs() <= 1);
+        let [(_, Term::Added(None, Some(to)))] = terms("added:>2w")[..] else {
+            panic!("added:>2w");
+        };
+        assert!((now - 14 * DAY - to).abs() <= 1);
+    }
+
+    #[test]
+    fn huge_ages_are_errors() {
+        assert!(Query::parse("added:<9223372036854775807y").is_err());
+        assert!(Query::parse("added:<99999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error_at("toon color:red"), 5);
+        assert_eq!(error_at(r#"a "sin cerrar"#), 2);
+        assert_eq!(error_at("a  -"), 3);
+        assert_eq!(error_at("tag:"), 0);
+        assert_eq!(error_at(r#"ñandú """#), 6);
+        assert_eq!(Query::parse("x color:red").unwrap_err().to_string(), "Carácter 3: campo desconocido 'color' (se puede usar tag, category, size, added y ext)");
+    }
+
+    #[test]
+    fn compile() {
+        let compiled = Query::parse(r#"toon "pelo largo" -old tag:nsfw size:>1KB"#).unwrap().compile();
+        assert_eq!(compiled.fts.as_deref(), Some(r#""toon"* "pelo largo""#));
+        assert_eq!(compiled.filter.split("\n AND ").count(), 3);
+        assert!(compiled.filter.starts_with("NOT (files.rowid IN (SELECT rowid FROM files_fts WHERE files_fts MATCH :p0))"));
+        assert_eq!(
+            compiled.params,
+            vec![
+                (":p0".to_string(), Value::Text(r#""old"*"#.into())),
+                (":p1".to_string(), Value::Text("nsfw".into())),
+                (":p2".to_string(), Value::Integer(1024)),
+            ]
+        );
+
+        // Ni las comillas ni los operadores FTS5 se salen de la cadena
+        assert_eq!(Term::Word(r#"a"b"#.into()).fts(), r#""a""b"*"#);
+        let compiled = Query::parse("a OR NEAR(b)").unwrap().compile();
+        assert_eq!(compiled.fts.as_deref(), Some(r#""a"* "OR"* "NEAR(b)"*"#));
+
+        let empty = Query::parse("  ").unwrap().compile();
+        assert_eq!((empty.fts, empty.filter.as_str(), empty.params.len()), (None, "1", 0));
+    }
+}
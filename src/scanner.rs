@@ -0,0 +1,436 @@
+use super::models::{FileRole, IndexedFile, ItemVersion};
+use super::sqlite_store::SqliteStore;
+use super::unitypackage;
+use super::versions;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+use walkdir::{DirEntry, WalkDir};
+
+/// Resumen de un escaneo de la carpeta principal
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanSummary {
+    pub new: usize,
+    pub modified: usize,
+    pub missing: usize,
+    pub unchanged: usize,
+    /// El escaneo se canceló antes de terminar; no se eliminó ninguna fila
+    pub cancelled: bool,
+    /// Carpetas o paquetes que no se pudieron leer
+    pub warnings: Vec<String>,
+}
+
+/// Tamaño, fecha y hash opcional del contenido de una carpeta de item
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FolderMetadata {
+    pub size: u64,
+    pub mtime: i64,
+    pub hash: Option<String>,
+}
+
+/// Recorre `root` buscando carpetas de item (las subcarpetas de cada categoría de `categories` que
+/// no son a su vez categorías) y las sincroniza con la tabla `files`.
+///
+/// Solo se reescriben las filas cuyo tamaño o fecha cambiaron; si `hash_contents` está activo también
+/// se calcula el hash de esas carpetas (y de las que aún no tienen uno). Los tags ya guardados se
+/// conservan y las filas bajo `root` cuya carpeta ya no existe se eliminan.
+///
+/// `on_progress` recibe (procesadas, total, nombre actual) por cada carpeta; si `cancel` se activa
+/// el escaneo se detiene y devuelve lo hecho hasta ese momento.
+pub fn scan_library(
+    root: &Path,
+    store: &SqliteStore,
+    categories: &[String],
+    hash_contents: bool,
+    cancel: &AtomicBool,
+    on_progress: &mut dyn FnMut(usize, usize, &str),
+) -> Result<ScanSummary> {
+    let mut summary = ScanSummary::default();
+
+    let mut known: HashMap<String, IndexedFile> = store
+        .get_files()?
+        .into_iter()
+        .filter(|f| Path::new(&f.path).starts_with(root))
+        .map(|f| (f.path.clone(), f))
+        .collect();
+
+    let folders = item_folders(root, categories, &mut summary.warnings);
+    let total = folders.len();
+
+    for (i, entry) in folders.into_iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            summary.cancelled = true;
+            return Ok(summary);
+        }
+
+        let path = entry.path().display().to_string();
+        let name = entry.file_name().to_string_lossy().to_string();
+        on_progress(i, total, &name);
+        let mut meta = folder_metadata(entry.path());
+
+        match known.remove(&path) {
+            Some(existing) => {
+                let stale = existing.size != meta.size || existing.mtime != meta.mtime || existing.name != name;
+                let needs_hash = hash_contents && (stale || existing.hash.is_none());
+                if !stale && !needs_hash {
+                    summary.unchanged += 1;
+                    continue;
+                }
+
+                // Un hash anterior solo vale si la carpeta no cambió
+                meta.hash = if needs_hash {
+                    hash_folder(entry.path()).ok()
+                } else if stale {
+                    None
+                } else {
+                    existing.hash.clone()
+                };
+                // Calcular el hash por primera vez no cuenta como modificación
+                let modified = stale || (existing.hash.is_some() && meta.hash != existing.hash);
+                store.insert_file(&IndexedFile {
+                    path: path.clone(),
+                    name,
+                    tags: existing.tags,
+                    size: meta.size,
+                    mtime: meta.mtime,
+                    hash: meta.hash,
+                    version: None,
+                })?;
+                if stale {
+                    index_packages(store, &path, entry.path(), &mut summary.warnings)?;
+                    store.sync_versions(&path, &package_versions(&path, entry.path()))?;
+                }
+                if modified {
+                    summary.modified += 1;
+                } else {
+                    summary.unchanged += 1;
+                }
+            }
+            None => {
+                if hash_contents {
+                    meta.hash = hash_folder(entry.path()).ok();
+                }
+                store.insert_file(&IndexedFile {
+                    path: path.clone(),
+                    name,
+                    tags: Vec::new(),
+                    size: meta.size,
+                    mtime: meta.mtime,
+                    hash: meta.hash,
+                    version: None,
+                })?;
+                index_packages(store, &path, entry.path(), &mut summary.warnings)?;
+                store.sync_versions(&path, &package_versions(&path, entry.path()))?;
+                summary.new += 1;
+            }
+        }
+    }
+
+    on_progress(total, total, "");
+
+    // Lo que queda en `known` ya no existe en disco
+    for path in known.keys() {
+        store.delete_file(path)?;
+        summary.missing += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Carpeta de la categoría con ruta `path` (`Avatars/Base Models`) dentro de `root`
+pub fn category_dir(root: &Path, path: &str) -> PathBuf {
+    path.split('/').fold(root.to_path_buf(), |dir, name| dir.join(name))
+}
+
+/// Nombre que se puede usar como carpeta de categoría o de item: un solo nivel y no oculta
+pub fn is_valid_folder_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// Carpetas de item bajo `root`, ignorando carpetas ocultas. `categories` son las rutas de las
+/// categorías registradas; las carpetas de primer nivel se consideran categorías siempre.
+/// Las carpetas que no se pueden leer van a `warnings`.
+pub fn item_folders(root: &Path, categories: &[String], warnings: &mut Vec<String>) -> Vec<DirEntry> {
+    let mut known: HashSet<String> = categories.iter().cloned().collect();
+    for entry in child_dirs(root, warnings) {
+        known.insert(entry.file_name().to_string_lossy().to_string());
+    }
+
+    let mut paths: Vec<&String> = known.iter().collect();
+    paths.sort();
+
+    let mut folders = Vec::new();
+    for path in paths {
+        let dir = category_dir(root, path);
+        for entry in child_dirs(&dir, warnings) {
+            let child = format!("{}/{}", path, entry.file_name().to_string_lossy());
+            if !known.contains(&child) {
+                folders.push(entry);
+            }
+        }
+    }
+    folders
+}
+
+/// Rutas de las categorías que hay en disco: las carpetas de primer nivel y las de `known` que
+/// siguen existiendo. Cualquier otra carpeta dentro de una categoría es un item, aunque solo tenga
+/// subcarpetas (`Outfit/{Textures,Prefabs}`); las subcategorías se crean desde la aplicación.
+pub fn category_folders(root: &Path, known: &[String]) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut warnings = Vec::new();
+    let mut pending: Vec<String> = child_dirs(root, &mut warnings)
+        .into_iter()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+
+    while let Some(path) = pending.pop() {
+        for entry in child_dirs(&category_dir(root, &path), &mut warnings) {
+            let child = format!("{}/{}", path, entry.file_name().to_string_lossy());
+            if known.contains(&child) {
+                pending.push(child);
+            }
+        }
+        found.push(path);
+    }
+    found.sort();
+    found
+}
+
+/// Subcarpetas no ocultas de `dir`
+fn child_dirs(dir: &Path, warnings: &mut Vec<String>) -> Vec<DirEntry> {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !is_hidden(e))
+        .filter_map(|e| match e {
+            Ok(e) => Some(e),
+            Err(e) => {
+                warnings.push(format!("Error leyendo {}: {}", dir.display(), e));
+                None
+            }
+        })
+        .filter(|e| e.file_type().is_dir())
+        .collect()
+}
+
+/// Suma el tamaño de todos los archivos de la carpeta y toma la fecha de modificación más reciente
+pub fn folder_metadata(dir: &Path) -> FolderMetadata {
+    let mut meta = FolderMetadata::default();
+    for entry in WalkDir::new(dir).into_iter().flatten() {
+        let Ok(m) = entry.metadata() else { continue };
+        if m.is_file() {
+            meta.size += m.len();
+        }
+        if let Some(secs) = m.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+            meta.mtime = meta.mtime.max(secs.as_secs() as i64);
+        }
+    }
+    meta
+}
+
+/// Lee el contenido de los `.unitypackage` de la carpeta del item y lo guarda en `package_assets`.
+/// Devuelve cuántos assets se indexaron; los paquetes ilegibles van a `warnings`.
+pub fn index_packages(store: &SqliteStore, item_path: &str, dir: &Path, warnings: &mut Vec<String>) -> Result<usize> {
+    let mut assets = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name().into_iter().flatten() {
+        if !entry.file_type().is_file() || !unitypackage::is_unitypackage(entry.path()) {
+            continue;
+        }
+        match unitypackage::read_assets(entry.path()) {
+            Ok(found) => assets.extend(found),
+            Err(e) => warnings.push(format!("Error leyendo paquete {}: {}", entry.path().display(), e)),
+        }
+    }
+    store.set_package_assets(item_path, &assets)?;
+    Ok(assets.len())
+}
+
+/// Versiones que se deducen de los nombres de los paquetes de la carpeta del item, con la fecha de
+/// modificación de cada archivo como fecha de importación
+pub fn package_versions(item_path: &str, dir: &Path) -> Vec<ItemVersion> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !is_hidden(e))
+        .flatten()
+        .filter(|e| e.file_type().is_file() && versions::is_release(e.path()))
+        .filter_map(|e| {
+            let version = versions::detect(e.path())?;
+            let mtime = e.metadata().ok()?.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+            Some(ItemVersion {
+                item_path: item_path.to_string(),
+                file_path: e.path().display().to_string(),
+                version,
+                imported_at: mtime,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Imagen representativa de la carpeta de un item: la llamada `preview`/`image`/`thumbnail`/`icon`
+/// si existe, si no la primera imagen en orden alfabético
+pub fn find_item_image(dir: &Path) -> Option<std::path::PathBuf> {
+    let mut images: Vec<_> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && FileRole::detect(p) == FileRole::Image)
+        .collect();
+    images.sort();
+
+    let preferred = ["preview", "image", "thumbnail", "icon"];
+    let stem = |p: &std::path::PathBuf| p.file_stem().map(|s| s.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    images
+        .iter()
+        .find(|p| preferred.contains(&stem(p).as_str()))
+        .or_else(|| images.first())
+        .cloned()
+}
+
+/// Hash BLAKE3 de los archivos de la carpeta (ruta relativa + contenido, en orden)
+pub fn hash_folder(dir: &Path) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    for entry in WalkDir::new(dir).sort_by_file_name().into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update_reader(File::open(entry.path())?)?;
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_name().to_str().is_some_and(|s| s.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LibraryPaths;
+    use crate::database::Database;
+    use std::fs;
+
+    /// Biblioteca en una carpeta temporal con `Avatars/Outfit` y `Props/Chair`
+    fn library(name: &str) -> (PathBuf, Database) {
+        let root = std::env::temp_dir().join(format!("vrcfm-scanner-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&root);
+        for (dir, file) in [("Avatars/Outfit", "Outfit.zip"), ("Props/Chair", "Chair.zip")] {
+            let dir = category_dir(&root, dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(file), b"contenido").unwrap();
+        }
+        let db = Database::open_library(&LibraryPaths::new(&root)).unwrap().db;
+        (root, db)
+    }
+
+    fn scan(root: &Path, db: &Database, hash_contents: bool, cancel: bool) -> ScanSummary {
+        let categories: Vec<String> = db.categories().get_categories().unwrap().into_iter().map(|c| c.path).collect();
+        scan_library(root, &db.files(), &categories, hash_contents, &AtomicBool::new(cancel), &mut |_, _, _| {}).unwrap()
+    }
+
+    fn indexed(db: &Database) -> Vec<String> {
+        let mut paths: Vec<String> = db.files().get_files().unwrap().into_iter().map(|f| f.path).collect();
+        paths.sort();
+        paths
+    }
+
+    fn hash(db: &Database, dir: &Path) -> Option<String> {
+        db.files().get_file(&dir.display().to_string()).unwrap().unwrap().hash
+    }
+
+    #[test]
+    fn new_unchanged_modified_and_missing() {
+        let (root, db) = library("summary");
+        let outfit = category_dir(&root, "Avatars/Outfit");
+        let chair = category_dir(&root, "Props/Chair");
+
+        let summary = scan(&root, &db, false, false);
+        assert_eq!((summary.new, summary.modified, summary.missing, summary.unchanged), (2, 0, 0, 0));
+        assert_eq!(indexed(&db), [outfit.display().to_string(), chair.display().to_string()]);
+
+        let summary = scan(&root, &db, false, false);
+        assert_eq!((summary.new, summary.modified, summary.missing, summary.unchanged), (0, 0, 0, 2));
+
+        fs::write(outfit.join("readme.txt"), b"nuevo").unwrap();
+        fs::remove_dir_all(&chair).unwrap();
+        let summary = scan(&root, &db, false, false);
+        assert_eq!((summary.new, summary.modified, summary.missing, summary.unchanged), (0, 1, 1, 0));
+        assert!(!summary.cancelled);
+        assert_eq!(indexed(&db), [outfit.display().to_string()]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn folders_with_only_subfolders_are_items() {
+        let (root, db) = library("layout");
+        for dir in ["Avatars/Kit/Textures", "Avatars/Kit/Prefabs", "Avatars/Manuka/Hair/Textures"] {
+            fs::create_dir_all(category_dir(&root, dir)).unwrap();
+            fs::write(category_dir(&root, dir).join("a.png"), b"png").unwrap();
+        }
+        // `Avatars/Manuka` es una subcategoría creada desde la aplicación
+        db.categories().ensure_path("Avatars/Manuka").unwrap();
+
+        let known: Vec<String> = db.categories().get_categories().unwrap().into_iter().map(|c| c.path).collect();
+        let on_disk = category_folders(&root, &known);
+        assert_eq!(on_disk, ["Avatars", "Avatars/Manuka", "Props"]);
+        db.categories().sync_paths(&on_disk).unwrap();
+
+        scan(&root, &db, false, false);
+        let path = |dir: &str| category_dir(&root, dir).display().to_string();
+        let mut expected = [path("Avatars/Kit"), path("Avatars/Manuka/Hair"), path("Avatars/Outfit"), path("Props/Chair")];
+        expected.sort();
+        assert_eq!(indexed(&db), expected);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn cancelled_scan_deletes_nothing() {
+        let (root, db) = library("cancel");
+        scan(&root, &db, false, false);
+
+        fs::remove_dir_all(category_dir(&root, "Props/Chair")).unwrap();
+        let summary = scan(&root, &db, false, true);
+        assert!(summary.cancelled);
+        assert_eq!((summary.new, summary.modified, summary.missing), (0, 0, 0));
+        assert_eq!(indexed(&db).len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn hashes_survive_scans_without_hashing() {
+        let (root, db) = library("hash");
+        let outfit = category_dir(&root, "Avatars/Outfit");
+
+        scan(&root, &db, true, false);
+        let first = hash(&db, &outfit);
+        assert!(first.is_some());
+
+        // Sin cambios el hash guardado se conserva aunque no se calcule
+        let summary = scan(&root, &db, false, false);
+        assert_eq!(summary.unchanged, 2);
+        assert_eq!(hash(&db, &outfit), first);
+
+        // Si la carpeta cambió el hash anterior ya no vale
+        fs::write(outfit.join("readme.txt"), b"nuevo").unwrap();
+        scan(&root, &db, false, false);
+        assert_eq!(hash(&db, &outfit), None);
+
+        let summary = scan(&root, &db, true, false);
+        assert_eq!((summary.modified, summary.unchanged), (0, 2));
+        assert!(hash(&db, &outfit).is_some_and(|h| Some(&h) != first.as_ref()));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
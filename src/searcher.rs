@@ -0,0 +1,158 @@
+use eframe::egui;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use vrcfm::database::Database;
+use vrcfm::error::StoreResult;
+use vrcfm::models::{PackageAsset, SavedSearch, SearchHit};
+use vrcfm::query::Query;
+
+/// Resultados que se piden de una vez; los siguientes se cargan al llegar al final de la lista
+pub const PAGE_SIZE: usize = 200;
+
+/// Assets de paquetes que se muestran como máximo
+pub const ASSET_LIMIT: usize = 500;
+
+enum Request {
+    Search { generation: u64, query: Query, offset: usize },
+    /// Búsquedas guardadas (id y texto) que hay que contar
+    Count { generation: u64, searches: Vec<(i64, String)> },
+}
+
+/// Cuántas carpetas devuelve cada búsqueda guardada (`None` si ya no se entiende)
+pub type SavedCounts = HashMap<i64, Option<usize>>;
+
+/// Página de resultados de una búsqueda
+pub struct SearchPage {
+    /// Posición del primer resultado; 0 si es una búsqueda nueva
+    pub offset: usize,
+    pub hits: StoreResult<Vec<SearchHit>>,
+    /// Solo se buscan con la primera página
+    pub assets: StoreResult<Vec<PackageAsset>>,
+}
+
+impl SearchPage {
+    /// Si puede haber más resultados después de esta página
+    pub fn has_more(&self) -> bool {
+        self.hits.as_ref().is_ok_and(|hits| hits.len() == PAGE_SIZE)
+    }
+}
+
+/// Hilo que ejecuta las búsquedas con su propia conexión, para no parar la UI mientras se escribe.
+/// Si llegan varias peticiones seguidas solo se atiende la última de cada tipo.
+pub struct Searcher {
+    tx: Sender<Request>,
+    rx: Receiver<(u64, SearchPage)>,
+    counts: Receiver<(u64, SavedCounts)>,
+    /// Búsqueda en curso; las páginas de búsquedas anteriores se descartan
+    generation: u64,
+    query: Query,
+    /// Último recuento pedido; los anteriores se descartan
+    count_generation: u64,
+}
+
+impl Searcher {
+    pub fn spawn(db_path: &str, ctx: &egui::Context) -> Self {
+        let (tx, requests) = mpsc::channel::<Request>();
+        let (pages, rx) = mpsc::channel();
+        let (count_tx, counts) = mpsc::channel();
+
+        let db_path = db_path.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let mut db: Option<Database> = None;
+            while let Ok(request) = requests.recv() {
+                let (mut search, mut count) = (None, None);
+                for request in std::iter::once(request).chain(requests.try_iter()) {
+                    match request {
+                        Request::Search { generation, query, offset } => search = Some((generation, query, offset)),
+                        Request::Count { generation, searches } => count = Some((generation, searches)),
+                    }
+                }
+
+                // Primero lo que está esperando quien escribe en el buscador
+                if let Some((generation, query, offset)) = search {
+                    let page = match open(&mut db, &db_path) {
+                        Ok(db) => SearchPage {
+                            offset,
+                            hits: db.files().search(&query, offset, PAGE_SIZE),
+                            assets: if offset == 0 { db.files().find_assets(&query, ASSET_LIMIT) } else { Ok(Vec::new()) },
+                        },
+                        Err(e) => SearchPage { offset, hits: Err(e), assets: Ok(Vec::new()) },
+                    };
+                    if pages.send((generation, page)).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+
+                if let Some((generation, searches)) = count {
+                    let db = open(&mut db, &db_path).ok();
+                    let counts = searches
+                        .into_iter()
+                        .map(|(id, text)| {
+                            let count = db.zip(Query::parse(&text).ok()).and_then(|(db, query)| db.files().count(&query).ok());
+                            (id, count)
+                        })
+                        .collect();
+                    if count_tx.send((generation, counts)).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        });
+
+        Self { tx, rx, counts, generation: 0, query: Query::default(), count_generation: 0 }
+    }
+
+    /// Empieza una búsqueda nueva desde el primer resultado
+    pub fn search(&mut self, query: Query) {
+        self.generation += 1;
+        self.query = query;
+        self.send(0);
+    }
+
+    /// Pide la página que empieza en `offset` de la búsqueda en curso
+    pub fn load_more(&self, offset: usize) {
+        self.send(offset);
+    }
+
+    /// Páginas de la búsqueda en curso que ya llegaron, sin bloquear
+    pub fn poll(&self) -> Vec<SearchPage> {
+        self.rx
+            .try_iter()
+            .filter(|(generation, _)| *generation == self.generation)
+            .map(|(_, page)| page)
+            .collect()
+    }
+
+    /// Pide cuántas carpetas devuelve ahora cada búsqueda guardada
+    pub fn count(&mut self, searches: &[SavedSearch]) {
+        self.count_generation += 1;
+        let searches = searches.iter().map(|saved| (saved.id, saved.query.clone())).collect();
+        let _ = self.tx.send(Request::Count { generation: self.count_generation, searches });
+    }
+
+    /// El último recuento pedido, si ya llegó
+    pub fn poll_counts(&self) -> Option<SavedCounts> {
+        self.counts
+            .try_iter()
+            .filter(|(generation, _)| *generation == self.count_generation)
+            .map(|(_, counts)| counts)
+            .last()
+    }
+
+    fn send(&self, offset: usize) {
+        let request = Request::Search { generation: self.generation, query: self.query.clone(), offset };
+        let _ = self.tx.send(request);
+    }
+}
+
+/// La conexión se abre con la primera búsqueda y se reintenta si falló
+fn open<'a>(db: &'a mut Option<Database>, db_path: &str) -> StoreResult<&'a Database> {
+    Ok(match db {
+        Some(db) => db,
+        None => db.insert(Database::open(db_path)?),
+    })
+}
@@ -1,49 +1,584 @@
-use super::models::IndexedFile;
-use anyhow::Result;
-use rusqlite::{params, Connection};
+use super::models::{DuplicateGroup, FileHash, FileLink, IndexedFile, ItemVersion, PackageAsset, SavedFolder, SearchHit};
+use super::database::savepoint;
+use super::error::StoreResult;
+use super::library::LibraryRoot;
+use super::query::{CompiledQuery, Query};
+use super::versions;
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use std::path::{Path, PathBuf};
 
-pub struct SqliteStore {
-    conn: Connection,
+/// Columnas de `files` en el orden de `row_to_file`; los tags salen de `file_tags` como JSON
+const FILE_COLUMNS: &str = r#"
+    path, name,
+    (SELECT json_group_array(t.name) FROM file_tags ft JOIN tags t ON t.id = ft.tag_id WHERE ft.file_path = files.path),
+    size, mtime, hash,
+    (SELECT version FROM item_versions WHERE item_path = files.path AND is_current)
+"#;
+
+/// Marcan el principio y el final de cada coincidencia en [`SearchHit::snippet`]
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+/// Las coincidencias en el contenido de paquetes pesan menos que en la propia carpeta
+const ASSET_RANK_WEIGHT: f64 = 0.5;
+
+/// Parámetros con nombre de las condiciones de `query`, para pasarlos a rusqlite
+fn named_params(query: &CompiledQuery) -> Vec<(&str, &dyn ToSql)> {
+    query.params.iter().map(|(name, value)| (name.as_str(), value as &dyn ToSql)).collect()
+}
+
+/// Tablas `files`, `file_tags`, `package_assets`, `file_hashes`, `file_links` e `item_versions`; se
+/// obtiene con `Database::files`. Recibe y devuelve rutas completas; en la base se guardan relativas
+/// a la carpeta principal.
+pub struct SqliteStore<'a> {
+    conn: &'a Connection,
+    root: &'a LibraryRoot,
 }
 
-impl SqliteStore {
-    pub fn new(path: &str) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        conn.execute_batch(
+impl<'a> SqliteStore<'a> {
+    pub fn new(conn: &'a Connection, root: &'a LibraryRoot) -> Self {
+        Self { conn, root }
+    }
+
+    pub fn insert_file(&self, f: &IndexedFile) -> StoreResult<()> {
+        savepoint(self.conn, || {
+            // Un UPSERT en vez de REPLACE: REPLACE borra la fila y con ella (en cascada) sus tags y assets
+            self.conn.execute(
+                // `added_at` solo se pone la primera vez
             r#"
-            CREATE TABLE IF NOT EXISTS files (
-                path TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                tags TEXT NOT NULL DEFAULT '[]'
-            );
+                INSERT INTO files (path, name, size, mtime, hash, added_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, CAST(strftime('%s', 'now') AS INTEGER))
+                ON CONFLICT(path) DO UPDATE SET
+                    name = excluded.name, size = excluded.size, mtime = excluded.mtime, hash = excluded.hash
+                "#,
+                params![self.root.relative(&f.path), f.name, f.size as i64, f.mtime, f.hash],
+            )?;
+            self.set_file_tags(&f.path, &f.tags)
+        })
+    }
+
+    /// Reemplaza los tags de una carpeta; los que no existan se crean
+    pub fn set_file_tags(&self, path: &str, tags: &[String]) -> StoreResult<()> {
+        let path = self.root.relative(path);
+        savepoint(self.conn, || {
+            self.conn.execute("DELETE FROM file_tags WHERE file_path = ?1", params![path])?;
+            for tag in tags {
+                self.conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO file_tags (file_path, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+                    params![path, tag],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Los tags y el contenido de paquetes se borran en cascada
+    pub fn delete_file(&self, path: &str) -> StoreResult<()> {
+        self.conn.execute("DELETE FROM files WHERE path = ?1", params![self.root.relative(path)])?;
+        Ok(())
+    }
+
+    /// Reemplaza el contenido indexado de los paquetes de un item
+    pub fn set_package_assets(&self, item_path: &str, assets: &[PackageAsset]) -> StoreResult<()> {
+        let item_path = self.root.relative(item_path);
+        savepoint(self.conn, || {
+            self.conn.execute("DELETE FROM package_assets WHERE item_path = ?1", params![item_path])?;
+            // UPSERT y no REPLACE: el borrado implícito de REPLACE no dispara los triggers de `assets_fts`
+            let mut stmt = self.conn.prepare(
+                r#"
+                INSERT INTO package_assets (item_path, package, guid, asset_path, size) VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(package, guid) DO UPDATE SET
+                    item_path = excluded.item_path, asset_path = excluded.asset_path, size = excluded.size
+                "#,
+            )?;
+            for a in assets {
+                stmt.execute(params![item_path, self.root.relative(&a.package), a.guid, a.asset_path, a.size as i64])?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Hasta `limit` assets cuya ruta contiene las palabras y frases de `query`, dentro de las carpetas
+    /// que cumplen sus filtros; los más relevantes primero. Sin palabras no devuelve nada.
+    pub fn find_assets(&self, query: &Query, limit: usize) -> StoreResult<Vec<PackageAsset>> {
+        let compiled = query.compile();
+        let Some(fts) = &compiled.fts else { return Ok(Vec::new()) };
+        let limit = limit as i64;
+        let mut params = named_params(&compiled);
+        params.push((":fts", fts));
+        params.push((":limit", &limit));
+
+        let mut stmt = self.conn.prepare(&format!(
+            r#"
+            SELECT a.package, a.guid, a.asset_path, a.size
+            FROM assets_fts
+            JOIN package_assets a ON a.rowid = assets_fts.rowid
+            JOIN files ON files.path = a.item_path
+            WHERE assets_fts MATCH :fts AND {filter}
+            ORDER BY bm25(assets_fts)
+            LIMIT :limit
             "#,
-        )?;
-        Ok(Self { conn })
+            filter = compiled.filter,
+        ))?;
+        let rows = stmt.query_map(params.as_slice(), |r| self.row_to_asset(r))?;
+
+        let mut v = Vec::new();
+        for r in rows { v.push(r?); }
+        Ok(v)
     }
 
-    pub fn insert_file(&mut self, f: &IndexedFile) -> Result<()> {
-        let tags_json = serde_json::to_string(&f.tags)?;
+    /// Contenido indexado de los paquetes de un item
+    fn get_package_assets(&self, item_path: &str) -> StoreResult<Vec<PackageAsset>> {
+        let mut stmt = self.conn.prepare("SELECT package, guid, asset_path, size FROM package_assets WHERE item_path = ?1")?;
+        let rows = stmt.query_map(params![self.root.relative(item_path)], |r| self.row_to_asset(r))?;
+
+        let mut v = Vec::new();
+        for r in rows { v.push(r?); }
+        Ok(v)
+    }
+
+    /// Tags, versiones y contenido de paquetes de las carpetas de item dentro de `dir`, con las
+    /// rutas relativas a `dir`; se recuperan con [`SqliteStore::restore_folders`]
+    pub fn saved_folders(&self, dir: &str) -> StoreResult<Vec<SavedFolder>> {
+        let folder = LibraryRoot::new(Some(PathBuf::from(dir)));
+        let mut saved = Vec::new();
+        for file in self.get_files()? {
+            if !Path::new(&file.path).starts_with(dir) {
+                continue;
+            }
+            let versions = self.get_versions(&file.path)?.into_iter()
+                .map(|v| ItemVersion { item_path: String::new(), file_path: folder.relative(&v.file_path), ..v })
+                .collect();
+            let assets = self.get_package_assets(&file.path)?.into_iter()
+                .map(|a| PackageAsset { package: folder.relative(&a.package), ..a })
+                .collect();
+            saved.push(SavedFolder { file: IndexedFile { path: folder.relative(&file.path), ..file }, versions, assets });
+        }
+        Ok(saved)
+    }
+
+    /// Vuelve a indexar las carpetas guardadas con [`SqliteStore::saved_folders`] dentro de `dir`,
+    /// con sus tags, sus versiones (fechas y notas incluidas) y el contenido de sus paquetes
+    pub fn restore_folders(&self, dir: &str, saved: &[SavedFolder]) -> StoreResult<()> {
+        let folder = LibraryRoot::new(Some(PathBuf::from(dir)));
+        savepoint(self.conn, || {
+            for s in saved {
+                let path = folder.resolve(&s.file.path);
+                self.insert_file(&IndexedFile { path: path.clone(), ..s.file.clone() })?;
+                for v in &s.versions {
+                    self.upsert_version(&ItemVersion { item_path: path.clone(), file_path: folder.resolve(&v.file_path), ..v.clone() })?;
+                }
+                self.refresh_current_version(&path)?;
+                let assets: Vec<PackageAsset> = s.assets.iter()
+                    .map(|a| PackageAsset { package: folder.resolve(&a.package), ..a.clone() })
+                    .collect();
+                self.set_package_assets(&path, &assets)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn get_files(&self) -> StoreResult<Vec<IndexedFile>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT {FILE_COLUMNS} FROM files ORDER BY path"))?;
+        let rows = stmt.query_map([], |r| self.row_to_file(r))?;
+
+        let mut v = Vec::new();
+        for r in rows { v.push(r?); }
+        Ok(v)
+    }
+
+    pub fn get_file(&self, path: &str) -> StoreResult<Option<IndexedFile>> {
+        let file = self.conn
+            .query_row(
+                &format!("SELECT {FILE_COLUMNS} FROM files WHERE path = ?1"),
+                params![self.root.relative(path)],
+                |r| self.row_to_file(r),
+            )
+            .optional()?;
+        Ok(file)
+    }
+
+    /// Carpetas que cumplen `query`. Las palabras y frases se buscan en el nombre, tags, categoría y
+    /// notas de cada carpeta y en las rutas de los assets de sus paquetes; los resultados van
+    /// ordenados por relevancia (bm25) y llevan el fragmento donde coincidieron. Sin palabras
+    /// devuelve las carpetas que cumplen los filtros por nombre.
+    ///
+    /// Devuelve como mucho `limit` resultados a partir del número `offset`.
+    pub fn search(&self, query: &Query, offset: usize, limit: usize) -> StoreResult<Vec<SearchHit>> {
+        let compiled = query.compile();
+        let (offset, limit) = (offset as i64, limit as i64);
+        let mut params = named_params(&compiled);
+        params.push((":offset", &offset));
+        params.push((":limit", &limit));
+        let filter = &compiled.filter;
+
+        // `path` desempata para que las páginas no se solapen
+        let Some(fts) = &compiled.fts else {
+            let sql = format!(
+                "SELECT {FILE_COLUMNS}, NULL FROM files WHERE {filter} ORDER BY name, path LIMIT :limit OFFSET :offset"
+            );
+            return self.query_hits(&sql, &params);
+        };
+        params.push((":fts", fts));
+        params.push((":asset_weight", &ASSET_RANK_WEIGHT));
+
+        // El nombre pesa más que los tags, y estos más que la categoría y las notas
+        let sql = format!(
+            r#"
+            SELECT {FILE_COLUMNS}, hits.snippet
+            FROM (
+                SELECT hit_path, min(rank) AS rank, snippet FROM (
+                    SELECT f.path AS hit_path, bm25(files_fts, 10.0, 5.0, 2.0, 1.0) AS rank,
+                           snippet(files_fts, -1, char(2), char(3), '…', 12) AS snippet
+                    FROM files_fts JOIN files f ON f.rowid = files_fts.rowid
+                    WHERE files_fts MATCH :fts
+                    UNION ALL
+                    SELECT a.item_path, bm25(assets_fts) * :asset_weight, highlight(assets_fts, 0, char(2), char(3))
+                    FROM assets_fts JOIN package_assets a ON a.rowid = assets_fts.rowid
+                    WHERE assets_fts MATCH :fts
+                )
+                GROUP BY hit_path
+            ) hits
+            JOIN files ON files.path = hits.hit_path
+            WHERE {filter}
+            ORDER BY hits.rank, files.path
+            LIMIT :limit OFFSET :offset
+            "#
+        );
+        self.query_hits(&sql, &params)
+    }
+
+    /// Cuántas carpetas devolvería `search` con esta búsqueda
+    pub fn count(&self, query: &Query) -> StoreResult<usize> {
+        let compiled = query.compile();
+        let mut params = named_params(&compiled);
+        let filter = &compiled.filter;
+        let sql = match &compiled.fts {
+            None => format!("SELECT count(*) FROM files WHERE {filter}"),
+            Some(fts) => {
+                params.push((":fts", fts));
+                format!(
+                    r#"
+                    SELECT count(*) FROM files
+                    WHERE (files.rowid IN (SELECT rowid FROM files_fts WHERE files_fts MATCH :fts)
+                           OR files.path IN (SELECT a.item_path FROM assets_fts JOIN package_assets a ON a.rowid = assets_fts.rowid
+                                             WHERE assets_fts MATCH :fts))
+                      AND {filter}
+                    "#
+                )
+            }
+        };
+        let count: i64 = self.conn.query_row(&sql, params.as_slice(), |r| r.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// `sql` devuelve las columnas de `FILE_COLUMNS` y después el fragmento
+    fn query_hits(&self, sql: &str, params: &[(&str, &dyn ToSql)]) -> StoreResult<Vec<SearchHit>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(params, |r| {
+            Ok(SearchHit { file: self.row_to_file(r)?, snippet: r.get(7)? })
+        })?;
+
+        let mut v = Vec::new();
+        for r in rows { v.push(r?); }
+        Ok(v)
+    }
+
+    /// Hashes guardados de los archivos de todas las carpetas
+    pub fn get_file_hashes(&self) -> StoreResult<Vec<FileHash>> {
+        let mut stmt = self.conn.prepare("SELECT path, item_path, size, mtime, hash FROM file_hashes")?;
+        let rows = stmt.query_map([], |r| {
+            Ok(FileHash {
+                path: self.root.resolve(&r.get::<_, String>(0)?),
+                item_path: self.root.resolve(&r.get::<_, String>(1)?),
+                size: r.get::<_, i64>(2)? as u64,
+                mtime: r.get(3)?,
+                hash: r.get(4)?,
+            })
+        })?;
+
+        let mut v = Vec::new();
+        for r in rows { v.push(r?); }
+        Ok(v)
+    }
+
+    pub fn set_file_hash(&self, h: &FileHash) -> StoreResult<()> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO files (path, name, tags) VALUES (?1, ?2, ?3)",
-            params![f.path, f.name, tags_json],
+            r#"
+            INSERT INTO file_hashes (path, item_path, size, mtime, hash) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(path) DO UPDATE SET
+                item_path = excluded.item_path, size = excluded.size, mtime = excluded.mtime, hash = excluded.hash
+            "#,
+            params![self.root.relative(&h.path), self.root.relative(&h.item_path), h.size as i64, h.mtime, h.hash],
         )?;
         Ok(())
     }
 
-    pub fn search(&self, query: &str) -> Result<Vec<IndexedFile>> {
-        let mut stmt = self.conn.prepare("SELECT path, name, tags FROM files WHERE name LIKE ?1")?;
-        let rows = stmt.query_map([format!("%{}%", query)], |r| {
-            let tags_json: String = r.get(2)?;
-            let tags = serde_json::from_str(&tags_json).unwrap_or_default();
-            Ok(IndexedFile {
-                path: r.get(0)?,
-                name: r.get(1)?,
-                tags,
+    pub fn delete_file_hash(&self, path: &str) -> StoreResult<()> {
+        self.conn.execute("DELETE FROM file_hashes WHERE path = ?1", params![self.root.relative(path)])?;
+        Ok(())
+    }
+
+    /// Grupos de archivos con el mismo contenido, los que más espacio desperdician primero
+    pub fn duplicate_groups(&self) -> StoreResult<Vec<DuplicateGroup>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT hash, max(size), json_group_array(path) FROM (SELECT * FROM file_hashes ORDER BY path)
+            GROUP BY hash HAVING count(*) > 1
+            ORDER BY max(size) * (count(*) - 1) DESC
+            "#,
+        )?;
+        let rows = stmt.query_map([], |r| {
+            let paths: String = r.get(2)?;
+            let paths: Vec<String> = serde_json::from_str(&paths).unwrap_or_default();
+            Ok(DuplicateGroup {
+                hash: r.get(0)?,
+                size: r.get::<_, i64>(1)? as u64,
+                paths: paths.iter().map(|p| self.root.resolve(p)).collect(),
+            })
+        })?;
+
+        let mut v = Vec::new();
+        for r in rows { v.push(r?); }
+        Ok(v)
+    }
+
+    /// Registra que la copia en `path` se eliminó y que la base debe usar `target` en su lugar:
+    /// los items, sus archivos, sus versiones y el contenido de paquetes pasan a apuntar a `target`.
+    /// Lo que ya existía para `target` se conserva y lo repetido de `path` se descarta.
+    pub fn link_duplicate(&self, path: &str, target: &str) -> StoreResult<()> {
+        let (path, target) = (self.root.relative(path), self.root.relative(target));
+        savepoint(self.conn, || {
+            self.conn.execute(
+                r#"
+                INSERT OR REPLACE INTO file_links (path, item_path, target, hash, created_at)
+                SELECT path, item_path, ?2, hash, CAST(strftime('%s', 'now') AS INTEGER) FROM file_hashes WHERE path = ?1
+                "#,
+                params![path, target],
+            )?;
+            self.conn.execute("DELETE FROM file_hashes WHERE path = ?1", params![path])?;
+            for (table, column) in [("items", "file_path"), ("items", "image_path"), ("item_files", "path")] {
+                self.conn.execute(
+                    &format!("UPDATE {table} SET {column} = ?2 WHERE {column} = ?1"),
+                    params![path, target],
+                )?;
+            }
+            for (table, column) in [("item_versions", "file_path"), ("package_assets", "package")] {
+                self.conn.execute(
+                    &format!("UPDATE OR IGNORE {table} SET {column} = ?2 WHERE {column} = ?1"),
+                    params![path, target],
+                )?;
+                self.conn.execute(&format!("DELETE FROM {table} WHERE {column} = ?1"), params![path])?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Copias eliminadas por duplicadas, las más recientes primero
+    pub fn get_file_links(&self) -> StoreResult<Vec<FileLink>> {
+        let mut stmt = self.conn.prepare("SELECT path, target, created_at FROM file_links ORDER BY created_at DESC, path")?;
+        let rows = stmt.query_map([], |r| {
+            Ok(FileLink {
+                path: self.root.resolve(&r.get::<_, String>(0)?),
+                target: self.root.resolve(&r.get::<_, String>(1)?),
+                created_at: r.get(2)?,
+            })
+        })?;
+
+        let mut v = Vec::new();
+        for r in rows { v.push(r?); }
+        Ok(v)
+    }
+
+    /// Versiones de un item, la más nueva primero
+    pub fn get_versions(&self, item_path: &str) -> StoreResult<Vec<ItemVersion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, item_path, file_path, version, imported_at, changelog, is_current FROM item_versions WHERE item_path = ?1",
+        )?;
+        let rows = stmt.query_map(params![self.root.relative(item_path)], |r| {
+            Ok(ItemVersion {
+                id: r.get(0)?,
+                item_path: self.root.resolve(&r.get::<_, String>(1)?),
+                file_path: self.root.resolve(&r.get::<_, String>(2)?),
+                version: r.get(3)?,
+                imported_at: r.get(4)?,
+                changelog: r.get(5)?,
+                current: r.get(6)?,
             })
         })?;
-        
+
         let mut v = Vec::new();
         for r in rows { v.push(r?); }
+        versions::sort_newest_first(&mut v);
         Ok(v)
     }
-}
\ No newline at end of file
+
+    /// Añade una versión al item (o actualiza la del mismo archivo) y vuelve a marcar la actual.
+    /// Si `v.changelog` es `None` se conservan las notas que tuviera.
+    pub fn add_version(&self, v: &ItemVersion) -> StoreResult<()> {
+        savepoint(self.conn, || {
+            self.upsert_version(v)?;
+            self.refresh_current_version(&v.item_path)
+        })
+    }
+
+    /// Deja en el item solo las versiones de `found` (las encontradas en disco). Las que ya estaban
+    /// conservan su fecha y sus notas.
+    pub fn sync_versions(&self, item_path: &str, found: &[ItemVersion]) -> StoreResult<()> {
+        savepoint(self.conn, || {
+            for old in self.get_versions(item_path)? {
+                if !found.iter().any(|v| v.file_path == old.file_path) {
+                    self.conn.execute("DELETE FROM item_versions WHERE id = ?1", params![old.id])?;
+                }
+            }
+            for v in found {
+                self.upsert_version(v)?;
+            }
+            self.refresh_current_version(item_path)
+        })
+    }
+
+    pub fn set_version_changelog(&self, id: i64, changelog: Option<&str>) -> StoreResult<()> {
+        self.conn.execute("UPDATE item_versions SET changelog = ?2 WHERE id = ?1", params![id, changelog])?;
+        Ok(())
+    }
+
+    fn upsert_version(&self, v: &ItemVersion) -> StoreResult<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO item_versions (item_path, file_path, version, imported_at, changelog) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(item_path, file_path) DO UPDATE SET
+                version = excluded.version, changelog = coalesce(excluded.changelog, changelog)
+            "#,
+            params![self.root.relative(&v.item_path), self.root.relative(&v.file_path), v.version, v.imported_at, v.changelog],
+        )?;
+        Ok(())
+    }
+
+    /// `is_current` en la versión más nueva del item y en ninguna más
+    fn refresh_current_version(&self, item_path: &str) -> StoreResult<()> {
+        let current = self.get_versions(item_path)?.first().map(|v| v.id);
+        self.conn.execute(
+            "UPDATE item_versions SET is_current = (id IS ?2) WHERE item_path = ?1",
+            params![self.root.relative(item_path), current],
+        )?;
+        Ok(())
+    }
+
+    fn row_to_asset(&self, r: &rusqlite::Row) -> rusqlite::Result<PackageAsset> {
+        Ok(PackageAsset {
+            package: self.root.resolve(&r.get::<_, String>(0)?),
+            guid: r.get(1)?,
+            asset_path: r.get(2)?,
+            size: r.get::<_, i64>(3)? as u64,
+        })
+    }
+
+    fn row_to_file(&self, r: &rusqlite::Row) -> rusqlite::Result<IndexedFile> {
+        let tags_json: String = r.get(2)?;
+        let tags = serde_json::from_str(&tags_json).unwrap_or_default();
+        Ok(IndexedFile {
+            path: self.root.resolve(&r.get::<_, String>(0)?),
+            name: r.get(1)?,
+            tags,
+            size: r.get::<_, i64>(3)? as u64,
+            mtime: r.get(4)?,
+            hash: r.get(5)?,
+            version: r.get(6)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::LibraryPaths;
+    use crate::database::Database;
+    use crate::models::{FileHash, IndexedFile, ItemVersion, PackageAsset};
+    use crate::query::Query;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Biblioteca vacía en una carpeta temporal
+    fn library(name: &str) -> (PathBuf, Database) {
+        let root = std::env::temp_dir().join(format!("vrcfm-store-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let db = Database::open_library(&LibraryPaths::new(&root)).unwrap().db;
+        (root, db)
+    }
+
+    /// Indexa la carpeta de item `item` con un paquete `package` que tiene versión, notas y un asset
+    fn add_item(db: &Database, root: &Path, item: &str, package: &str, changelog: &str) {
+        let (item, package) = (root.join(item).to_string_lossy().into_owned(), root.join(package).to_string_lossy().into_owned());
+        let files = db.files();
+        files.insert_file(&IndexedFile { path: item.clone(), name: "item".into(), ..Default::default() }).unwrap();
+        let hash = FileHash { path: package.clone(), item_path: item.clone(), size: 9, mtime: 1, hash: "abc".into() };
+        files.set_file_hash(&hash).unwrap();
+        let version = ItemVersion {
+            item_path: item.clone(),
+            file_path: package.clone(),
+            version: "1.0".into(),
+            changelog: Some(changelog.into()),
+            ..Default::default()
+        };
+        files.add_version(&version).unwrap();
+        let asset = PackageAsset { package, guid: "guid".into(), asset_path: format!("Assets/{changelog}.prefab"), size: 1 };
+        files.set_package_assets(&item, &[asset]).unwrap();
+    }
+
+    fn path(root: &Path, relative: &str) -> String {
+        root.join(relative).to_string_lossy().into_owned()
+    }
+
+    fn asset_packages(db: &Database) -> Vec<(String, String)> {
+        let assets = db.files().find_assets(&Query::parse("prefab").unwrap(), 10).unwrap();
+        assets.into_iter().map(|a| (a.package, a.asset_path)).collect()
+    }
+
+    #[test]
+    fn references_move_to_the_kept_copy() {
+        let (root, db) = library("move");
+        add_item(&db, &root, "Avatars/Outfit", "Avatars/Outfit/Outfit.zip", "original");
+        add_item(&db, &root, "Avatars/Copy", "Avatars/Copy/Outfit.zip", "copia");
+        let (copy, kept) = (path(&root, "Avatars/Copy/Outfit.zip"), path(&root, "Avatars/Outfit/Outfit.zip"));
+
+        db.files().link_duplicate(&copy, &kept).unwrap();
+
+        let hashes: Vec<String> = db.files().get_file_hashes().unwrap().into_iter().map(|h| h.path).collect();
+        assert_eq!(hashes, [kept.as_str()]);
+        let links = db.files().get_file_links().unwrap();
+        assert_eq!((links[0].path.as_str(), links[0].target.as_str()), (copy.as_str(), kept.as_str()));
+        // La versión de la copia sigue en su item, con sus notas, pero apunta al archivo conservado
+        let versions = db.files().get_versions(&path(&root, "Avatars/Copy")).unwrap();
+        assert_eq!(versions[0].file_path, kept);
+        assert_eq!(versions[0].changelog.as_deref(), Some("copia"));
+        // Los dos paquetes tenían el mismo asset: se queda el del archivo conservado
+        assert_eq!(asset_packages(&db), [(kept.clone(), "Assets/original.prefab".to_string())]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn repeated_versions_keep_the_existing_row() {
+        let (root, db) = library("repeated");
+        add_item(&db, &root, "Avatars/Outfit", "Avatars/Outfit/Outfit.zip", "original");
+        let (copy, kept) = (path(&root, "Avatars/Outfit/Outfit (1).zip"), path(&root, "Avatars/Outfit/Outfit.zip"));
+        let version = ItemVersion {
+            item_path: path(&root, "Avatars/Outfit"),
+            file_path: copy.clone(),
+            version: "1.0".into(),
+            changelog: Some("copia".into()),
+            ..Default::default()
+        };
+        db.files().add_version(&version).unwrap();
+
+        db.files().link_duplicate(&copy, &kept).unwrap();
+
+        let versions = db.files().get_versions(&path(&root, "Avatars/Outfit")).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!((versions[0].file_path.as_str(), versions[0].changelog.as_deref()), (kept.as_str(), Some("original")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,171 @@
+use eframe::egui;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use vrcfm::scanner;
+
+/// Lado mayor de las miniaturas en píxeles
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Texturas que se mantienen en memoria a la vez
+const MAX_TEXTURES: usize = 400;
+
+/// Hilos que decodifican imágenes
+const LOADER_THREADS: usize = 2;
+
+struct Loaded {
+    source: PathBuf,
+    image: Option<egui::ColorImage>,
+    error: Option<String>,
+}
+
+/// Miniaturas de las imágenes de los items.
+///
+/// Las imágenes se decodifican y reducen en hilos aparte; el resultado se guarda en `cache_dir`
+/// como `<hash del archivo original>.png` para no volver a decodificar el original.
+pub struct ThumbnailCache {
+    /// carpeta de item -> imagen elegida para ella
+    item_images: HashMap<PathBuf, Option<PathBuf>>,
+    textures: HashMap<PathBuf, (egui::TextureHandle, u64)>,
+    pending: HashSet<PathBuf>,
+    failed: HashSet<PathBuf>,
+    errors: Vec<String>,
+    requests: Sender<PathBuf>,
+    results: Receiver<Loaded>,
+    frame: u64,
+}
+
+impl ThumbnailCache {
+    pub fn new(cache_dir: &Path, ctx: &egui::Context) -> Self {
+        let (req_tx, req_rx) = mpsc::channel::<PathBuf>();
+        let (res_tx, res_rx) = mpsc::channel();
+        let req_rx = Arc::new(Mutex::new(req_rx));
+
+        let _ = fs::create_dir_all(cache_dir);
+        for _ in 0..LOADER_THREADS {
+            let req_rx = req_rx.clone();
+            let res_tx = res_tx.clone();
+            let cache_dir = cache_dir.to_path_buf();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                loop {
+                    let source = match req_rx.lock().map(|rx| rx.recv()) {
+                        Ok(Ok(source)) => source,
+                        _ => break,
+                    };
+                    let (image, error) = load_thumbnail(&cache_dir, &source);
+                    if res_tx.send(Loaded { source, image, error }).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+        }
+
+        Self {
+            item_images: HashMap::new(),
+            textures: HashMap::new(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            errors: Vec::new(),
+            requests: req_tx,
+            results: res_rx,
+            frame: 0,
+        }
+    }
+
+    /// Sube a la GPU las miniaturas terminadas y libera las que llevan más tiempo sin usarse.
+    /// Se llama una vez por frame.
+    pub fn update(&mut self, ctx: &egui::Context) {
+        self.frame += 1;
+
+        for loaded in self.results.try_iter() {
+            self.pending.remove(&loaded.source);
+            self.errors.extend(loaded.error);
+            match loaded.image {
+                Some(image) => {
+                    let name = loaded.source.display().to_string();
+                    let texture = ctx.load_texture(name, image, egui::TextureOptions::LINEAR);
+                    self.textures.insert(loaded.source, (texture, self.frame));
+                }
+                None => {
+                    self.failed.insert(loaded.source);
+                }
+            }
+        }
+
+        if self.textures.len() > MAX_TEXTURES {
+            let mut by_age: Vec<(PathBuf, u64)> = self.textures.iter().map(|(k, (_, used))| (k.clone(), *used)).collect();
+            by_age.sort_by_key(|(_, used)| *used);
+            for (path, _) in by_age.into_iter().take(self.textures.len() - MAX_TEXTURES) {
+                self.textures.remove(&path);
+            }
+        }
+    }
+
+    /// Textura de la miniatura de `source`; si aún no está cargada se pide y devuelve `None`
+    pub fn get(&mut self, source: &Path) -> Option<egui::TextureHandle> {
+        if let Some((texture, used)) = self.textures.get_mut(source) {
+            *used = self.frame;
+            return Some(texture.clone());
+        }
+        if !self.pending.contains(source) && !self.failed.contains(source) {
+            self.pending.insert(source.to_path_buf());
+            let _ = self.requests.send(source.to_path_buf());
+        }
+        None
+    }
+
+    /// Miniatura de la imagen representativa de la carpeta de un item
+    pub fn get_for_item(&mut self, item_dir: &Path) -> Option<egui::TextureHandle> {
+        let image = self
+            .item_images
+            .entry(item_dir.to_path_buf())
+            .or_insert_with(|| scanner::find_item_image(item_dir))
+            .clone()?;
+        self.get(&image)
+    }
+
+    /// Errores de carga desde la última llamada
+    pub fn take_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Olvida errores e imágenes elegidas, p. ej. tras reescanear la biblioteca
+    pub fn clear_failed(&mut self) {
+        self.failed.clear();
+        self.item_images.clear();
+    }
+}
+
+/// Decodifica (o lee de la caché) la miniatura de `source`. Un fallo al escribir la caché no
+/// impide mostrar la miniatura, por eso imagen y error se devuelven por separado.
+fn load_thumbnail(cache_dir: &Path, source: &Path) -> (Option<egui::ColorImage>, Option<String>) {
+    let bytes = match fs::read(source) {
+        Ok(bytes) => bytes,
+        Err(e) => return (None, Some(format!("Error leyendo {}: {}", source.display(), e))),
+    };
+    let cached = cache_dir.join(format!("{}.png", blake3::hash(&bytes).to_hex()));
+
+    let mut error = None;
+    let thumbnail = match image::open(&cached) {
+        Ok(img) => img,
+        Err(_) => {
+            let img = match image::load_from_memory(&bytes) {
+                Ok(img) => img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE),
+                Err(e) => return (None, Some(format!("Imagen no válida {}: {}", source.display(), e))),
+            };
+            if let Err(e) = img.save(&cached) {
+                error = Some(format!("Error guardando miniatura {}: {}", cached.display(), e));
+            }
+            img
+        }
+    };
+
+    let rgba = thumbnail.to_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    (Some(egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_flat_samples().as_slice())), error)
+}
@@ -0,0 +1,125 @@
+use super::models::SavedFolder;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Carpeta (oculta) dentro de la carpeta principal donde se guardan las categorías eliminadas
+pub const TRASH_DIR: &str = ".trash";
+
+/// Carpeta enviada a la papelera. Se guarda como `.trash/<id>/` junto a `.trash/<id>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    /// Ruta original relativa a la carpeta principal
+    pub original: String,
+    /// Segundos desde UNIX_EPOCH
+    pub deleted_at: i64,
+    /// Lo que la base sabía de las carpetas de item que contiene
+    #[serde(default)]
+    pub folders: Vec<SavedFolder>,
+}
+
+impl TrashEntry {
+    pub fn path(&self, root: &Path) -> PathBuf {
+        trash_dir(root).join(&self.id)
+    }
+
+    fn meta_path(&self, root: &Path) -> PathBuf {
+        trash_dir(root).join(format!("{}.json", self.id))
+    }
+}
+
+pub fn trash_dir(root: &Path) -> PathBuf {
+    root.join(TRASH_DIR)
+}
+
+/// Mueve `root/<relative>` a la papelera; `folders` se guarda en sus metadatos
+pub fn move_to_trash(root: &Path, relative: &str, folders: Vec<SavedFolder>) -> io::Result<TrashEntry> {
+    let source = root.join(relative);
+    if !source.exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} no existe", source.display())));
+    }
+    fs::create_dir_all(trash_dir(root))?;
+
+    let deleted_at = chrono::Utc::now().timestamp();
+    let safe_name = relative.replace(['/', '\\'], "_");
+    let mut id = format!("{}-{}", deleted_at, safe_name);
+    let mut n = 2;
+    while trash_dir(root).join(&id).exists() {
+        id = format!("{}-{}-{}", deleted_at, safe_name, n);
+        n += 1;
+    }
+
+    let entry = TrashEntry { id, original: relative.to_string(), deleted_at, folders };
+    fs::rename(&source, entry.path(root))?;
+    if let Err(e) = fs::write(entry.meta_path(root), serde_json::to_string_pretty(&entry)?) {
+        // Sin metadatos no se podría restaurar: deshacer el movimiento
+        let _ = fs::rename(entry.path(root), &source);
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+/// Entradas de la papelera, las más recientes primero
+pub fn list(root: &Path) -> io::Result<Vec<TrashEntry>> {
+    let dir = trash_dir(root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for file in fs::read_dir(dir)?.flatten() {
+        let path = file.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            let contents = fs::read_to_string(&path)?;
+            if let Ok(entry) = serde_json::from_str::<TrashEntry>(&contents) {
+                entries.push(entry);
+            }
+        }
+    }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.deleted_at));
+    Ok(entries)
+}
+
+/// Devuelve la carpeta a su ubicación original. Falla si ya existe algo con ese nombre.
+pub fn restore(root: &Path, entry: &TrashEntry) -> io::Result<PathBuf> {
+    let target = root.join(&entry.original);
+    if target.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} ya existe; renómbrala antes de restaurar", target.display()),
+        ));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(entry.path(root), &target)?;
+    fs::remove_file(entry.meta_path(root))?;
+    Ok(target)
+}
+
+/// Borra definitivamente una entrada
+pub fn purge(root: &Path, entry: &TrashEntry) -> io::Result<()> {
+    let path = entry.path(root);
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        // Copias duplicadas sueltas
+        fs::remove_file(path)?;
+    }
+    fs::remove_file(entry.meta_path(root))
+}
+
+/// Borra definitivamente las entradas con más de `days` días. Devuelve cuántas se borraron.
+pub fn purge_older_than(root: &Path, days: u32) -> io::Result<usize> {
+    let limit = chrono::Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+    let mut purged = 0;
+    for entry in list(root)? {
+        if entry.deleted_at < limit {
+            purge(root, &entry)?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
@@ -0,0 +1,113 @@
+use super::models::PackageAsset;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path};
+
+/// Un `.unitypackage` es un tar.gz con entradas `<guid>/asset`, `<guid>/pathname` y `<guid>/asset.meta`.
+/// Solo se mira la extensión: cualquier `.tar.gz` tiene la misma cabecera, y así no se abre el archivo.
+pub fn is_unitypackage(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("unitypackage"))
+}
+
+/// Lee la lista de assets de un `.unitypackage` sin necesidad de Unity
+pub fn read_assets(path: &Path) -> Result<Vec<PackageAsset>> {
+    let file = File::open(path).with_context(|| format!("No se pudo abrir {}", path.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+
+    // guid -> (ruta dentro del proyecto, tamaño del asset)
+    let mut entries: HashMap<String, (Option<String>, u64)> = HashMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let Some((guid, kind)) = split_entry_path(&entry_path) else { continue };
+
+        match kind.as_str() {
+            "asset" => {
+                entries.entry(guid).or_default().1 = entry.size();
+            }
+            "pathname" => {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                // La primera línea es la ruta; algunas versiones añaden más datos después
+                let asset_path = contents.lines().next().unwrap_or_default().trim().to_string();
+                entries.entry(guid).or_default().0 = Some(asset_path);
+            }
+            _ => {}
+        }
+    }
+
+    let package = path.display().to_string();
+    let mut assets: Vec<PackageAsset> = entries
+        .into_iter()
+        .filter_map(|(guid, (asset_path, size))| {
+            Some(PackageAsset { package: package.clone(), guid, asset_path: asset_path?, size })
+        })
+        .collect();
+    assets.sort_by(|a, b| a.asset_path.cmp(&b.asset_path));
+    Ok(assets)
+}
+
+/// Busca la mejor imagen de vista previa embebida en el paquete.
+///
+/// Orden de preferencia: el icono del paquete (`.icon.png`), la vista previa de un prefab y por
+/// último la vista previa más grande.
+pub fn extract_preview(path: &Path) -> Result<Option<Vec<u8>>> {
+    let file = File::open(path).with_context(|| format!("No se pudo abrir {}", path.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+
+    let mut previews: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut pathnames: HashMap<String, String> = HashMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+
+        if entry_path.file_name().is_some_and(|n| n == ".icon.png") {
+            let mut icon = Vec::new();
+            entry.read_to_end(&mut icon)?;
+            return Ok(Some(icon));
+        }
+
+        let Some((guid, kind)) = split_entry_path(&entry_path) else { continue };
+        match kind.as_str() {
+            "preview.png" => {
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes)?;
+                previews.insert(guid, bytes);
+            }
+            "pathname" => {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                pathnames.insert(guid, contents.lines().next().unwrap_or_default().trim().to_string());
+            }
+            _ => {}
+        }
+    }
+
+    let is_prefab = |guid: &String| pathnames.get(guid).is_some_and(|p| p.to_ascii_lowercase().ends_with(".prefab"));
+    let best = previews
+        .into_iter()
+        .max_by_key(|(guid, bytes)| (is_prefab(guid), bytes.len()))
+        .map(|(_, bytes)| bytes);
+    Ok(best)
+}
+
+/// `./<guid>/asset` -> (`<guid>`, `asset`)
+fn split_entry_path(path: &Path) -> Option<(String, String)> {
+    let mut parts = path.components().filter_map(|c| match c {
+        Component::Normal(s) => s.to_str(),
+        _ => None,
+    });
+    let guid = parts.next()?;
+    let kind = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((guid.to_string(), kind.to_string()))
+}
@@ -0,0 +1,183 @@
+use super::models::ItemVersion;
+use std::cmp::Ordering;
+use std::path::Path;
+
+/// Extensiones de los archivos que se tratan como una versión publicada del item
+const RELEASE_EXTENSIONS: [&str; 4] = ["unitypackage", "zip", "rar", "7z"];
+
+/// Si el archivo es un paquete o un comprimido; los readme, imágenes, etc. no tienen versión
+pub fn is_release(path: &Path) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    RELEASE_EXTENSIONS.contains(&ext.as_str())
+}
+
+/// Versión en el nombre de un archivo: `Outfit_v1.2.unitypackage` -> `1.2`, `Hair ver2.0.1.zip` -> `2.0.1`.
+/// Se busca desde el final del nombre un término que empiece por `v`/`ver` seguido de un número, o
+/// un número con puntos.
+pub fn detect(path: &Path) -> Option<String> {
+    let (_, version) = split(path.file_stem()?.to_str()?)?;
+    Some(version.to_string())
+}
+
+/// Nombre del archivo sin extensión ni versión: `Outfit_v1.2.unitypackage` -> `Outfit`
+pub fn base_name(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let name = split(stem).map_or(stem, |(name, _)| name);
+    let name = name.trim_end_matches([' ', '_', '-', '(', '[']);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Separa el nombre en lo que va antes del término de la versión y la versión (sin `v`/`ver`)
+fn split(stem: &str) -> Option<(&str, &str)> {
+    let mut end = stem.len();
+    for token in stem.rsplit([' ', '_', '-', '(', ')', '[', ']']) {
+        let start = end - token.len();
+        // Los separadores ocupan un byte
+        end = start.saturating_sub(1);
+        let lower = token.to_ascii_lowercase();
+        let (prefixed, rest) = ["ver.", "ver", "v"]
+            .iter()
+            .find_map(|p| lower.strip_prefix(p).map(|rest| (true, rest)))
+            .unwrap_or((false, lower.as_str()));
+
+        let starts_with_digit = rest.starts_with(|c: char| c.is_ascii_digit());
+        let well_formed = rest.chars().all(|c| c.is_ascii_alphanumeric() || c == '.') && !rest.ends_with('.');
+        if starts_with_digit && well_formed && (prefixed || rest.contains('.')) {
+            // Se devuelve con las mayúsculas originales (`1.0RC`)
+            return Some((&stem[..start], &token[token.len() - rest.len()..]));
+        }
+    }
+    None
+}
+
+/// Orden natural de versiones: los números se comparan como números (`1.10` > `1.9`) y el texto
+/// sin distinguir mayúsculas
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (chunks(a), chunks(b));
+    for (x, y) in a.iter().zip(&b) {
+        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase()),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// La versión más nueva primero; con la misma versión, la importada más tarde
+pub fn sort_newest_first(versions: &mut [ItemVersion]) {
+    versions.sort_by(|a, b| {
+        compare(&b.version, &a.version)
+            .then(b.imported_at.cmp(&a.imported_at))
+            .then_with(|| b.file_path.cmp(&a.file_path))
+    });
+}
+
+/// Tramos de dígitos y de letras, sin los separadores
+fn chunks(version: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = None;
+    let mut digits = false;
+    for (i, c) in version.char_indices() {
+        if !c.is_alphanumeric() {
+            if let Some(s) = start.take() {
+                chunks.push(&version[s..i]);
+            }
+            continue;
+        }
+        match start {
+            Some(s) if c.is_ascii_digit() != digits => {
+                chunks.push(&version[s..i]);
+                start = Some(i);
+            }
+            None => start = Some(i),
+            _ => {}
+        }
+        digits = c.is_ascii_digit();
+    }
+    if let Some(s) = start {
+        chunks.push(&version[s..]);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected(name: &str) -> Option<String> {
+        detect(Path::new(name))
+    }
+
+    fn base(name: &str) -> Option<String> {
+        base_name(Path::new(name))
+    }
+
+    #[test]
+    fn prefixed_versions() {
+        assert_eq!(detected("Outfit v1.2.3.unitypackage").as_deref(), Some("1.2.3"));
+        assert_eq!(detected("Outfit_V2.unitypackage").as_deref(), Some("2"));
+        assert_eq!(detected("Hair ver2.0.1.zip").as_deref(), Some("2.0.1"));
+        assert_eq!(detected("Hair-ver.3.zip").as_deref(), Some("3"));
+        assert_eq!(detected("Shoes (v1.0RC).7z").as_deref(), Some("1.0RC"));
+        assert_eq!(base("Outfit v1.2.3.unitypackage").as_deref(), Some("Outfit"));
+        assert_eq!(base("Shoes (v1.0RC).7z").as_deref(), Some("Shoes"));
+    }
+
+    #[test]
+    fn bare_versions_need_a_dot() {
+        assert_eq!(detected("Outfit_1.2.unitypackage").as_deref(), Some("1.2"));
+        assert_eq!(base("Outfit_1.2.unitypackage").as_deref(), Some("Outfit"));
+        assert_eq!(detected("Outfit 1.2.3b.zip").as_deref(), Some("1.2.3b"));
+        assert_eq!(detected("Outfit_2.unitypackage"), None);
+    }
+
+    #[test]
+    fn names_without_version() {
+        for name in ["Outfit.unitypackage", "Outfit_Manuka.zip", "Outfit_1.2..zip", "Outfit_vx.zip", "README"] {
+            assert_eq!(detected(name), None, "{name}");
+        }
+        assert_eq!(base("Outfit_Manuka.zip").as_deref(), Some("Outfit_Manuka"));
+        // La última versión del nombre es la que cuenta
+        assert_eq!(detected("Kit v2 for Base v1.1.zip").as_deref(), Some("1.1"));
+        assert_eq!(base("Kit v2 for Base v1.1.zip").as_deref(), Some("Kit v2 for Base"));
+        // Un nombre que es solo la versión no deja nombre
+        assert_eq!(base("v1.0.zip"), None);
+    }
+
+    #[test]
+    fn release_extensions() {
+        assert!(is_release(Path::new("a/Outfit.UnityPackage")));
+        assert!(is_release(Path::new("Outfit.7z")));
+        assert!(!is_release(Path::new("readme.txt")));
+        assert!(!is_release(Path::new("unitypackage")));
+    }
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(compare("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare("1.2", "1.2.0"), Ordering::Less);
+        assert_eq!(compare("1.2.3b", "1.2.3"), Ordering::Greater);
+        assert_eq!(compare("1.2.3a", "1.2.3B"), Ordering::Less);
+        assert_eq!(compare("1.0rc", "1.0RC"), Ordering::Equal);
+        assert_eq!(compare("2", "10"), Ordering::Less);
+    }
+
+    #[test]
+    fn newest_first() {
+        let version = |id, version: &str, imported_at| ItemVersion {
+            id,
+            item_path: "Avatars/Outfit".into(),
+            file_path: format!("Avatars/Outfit/{id}.zip"),
+            version: version.into(),
+            imported_at,
+            changelog: None,
+            current: false,
+        };
+        let mut versions = vec![version(1, "1.9", 10), version(2, "1.10", 5), version(3, "1.9", 20), version(4, "1.2.3b", 30)];
+        sort_newest_first(&mut versions);
+        assert_eq!(versions.iter().map(|v| v.id).collect::<Vec<_>>(), [2, 3, 1, 4]);
+    }
+}
@@ -0,0 +1,401 @@
+use super::database::Database;
+use super::dedup;
+use super::models::{DuplicateGroup, FileRole, IndexedFile, Item, ItemVersion};
+use super::scanner::{self, ScanSummary};
+use super::unitypackage;
+use super::versions;
+use anyhow::{bail, Result};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+/// Trabajo pesado que se ejecuta fuera del hilo de la UI
+pub enum Task {
+    /// Indexar la carpeta principal (y opcionalmente calcular hashes)
+    Scan { root: PathBuf, hash_contents: bool },
+    /// Copiar los archivos de un item a su carpeta (con su nombre original) y guardarlo en la DB.
+    /// La carpeta es la del nombre del item dentro de su categoría en `root`.
+    /// Si `use_package_preview` está activo y hay un `.unitypackage` con vista previa embebida,
+    /// esa se usa como imagen y `image_src` queda como respaldo.
+    ///
+    /// Si la carpeta ya tiene un item, los archivos se añaden a ese item como una versión nueva con
+    /// `changelog` como notas.
+    ImportItem {
+        item: Box<Item>,
+        root: PathBuf,
+        data_srcs: Vec<PathBuf>,
+        image_src: Option<PathBuf>,
+        use_package_preview: bool,
+        collision: CollisionPolicy,
+        changelog: Option<String>,
+    },
+    /// Calcular el hash de los archivos de las carpetas indexadas y agrupar los repetidos
+    FindDuplicates,
+}
+
+/// Qué hacer cuando ya existe un archivo con el mismo nombre en la carpeta del item
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// Guardar como `nombre (2).ext`, `nombre (3).ext`...
+    #[default]
+    Rename,
+    Overwrite,
+    /// Conservar el archivo existente
+    Skip,
+}
+
+impl CollisionPolicy {
+    pub const ALL: [CollisionPolicy; 3] = [CollisionPolicy::Rename, CollisionPolicy::Overwrite, CollisionPolicy::Skip];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CollisionPolicy::Rename => "Renombrar",
+            CollisionPolicy::Overwrite => "Sobrescribir",
+            CollisionPolicy::Skip => "Conservar existente",
+        }
+    }
+
+    /// Ruta final para `file_name` dentro de `dir` y si hay que copiar el archivo
+    pub fn resolve(&self, dir: &Path, file_name: &OsStr) -> (PathBuf, bool) {
+        let target = dir.join(file_name);
+        if !target.exists() {
+            return (target, true);
+        }
+        match self {
+            CollisionPolicy::Overwrite => (target, true),
+            CollisionPolicy::Skip => (target, false),
+            CollisionPolicy::Rename => {
+                let name = Path::new(file_name);
+                let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+                let ext = name.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+                let renamed = (2..)
+                    .map(|n| dir.join(format!("{} ({}){}", stem, n, ext)))
+                    .find(|p| !p.exists())
+                    .unwrap_or(target);
+                (renamed, true)
+            }
+        }
+    }
+}
+
+/// Mensajes del hilo de trabajo hacia la UI
+pub enum WorkerEvent {
+    Progress { label: String, done: u64, total: u64 },
+    /// Problema no fatal; el trabajo sigue
+    Warning(String),
+    ScanFinished(ScanSummary),
+    ItemImported,
+    DuplicatesFound(Vec<DuplicateGroup>),
+    Cancelled,
+    Failed(String),
+}
+
+/// Hilo de trabajo en curso
+pub struct Worker {
+    rx: Receiver<WorkerEvent>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Worker {
+    /// Lanza `task` en un hilo nuevo con su propia conexión a `db_path`. `notify` se llama tras
+    /// cada mensaje (la interfaz lo usa para repintarse).
+    pub fn spawn(db_path: &str, task: Task, notify: impl Fn() + Send + 'static) -> Self {
+        Self::start(db_path, task, Some(Box::new(notify)))
+    }
+
+    /// Como `spawn` pero sin UI que repintar; los mensajes se leen con [`Worker::recv`]
+    pub fn spawn_headless(db_path: &str, task: Task) -> Self {
+        Self::start(db_path, task, None)
+    }
+
+    fn start(db_path: &str, task: Task, notify: Option<Box<dyn Fn() + Send>>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let db_path = db_path.to_string();
+        let flag = cancel.clone();
+        thread::spawn(move || {
+            let reporter = Reporter { tx, notify };
+            let event = match run(&db_path, task, &flag, &reporter) {
+                Ok(event) => event,
+                Err(e) => WorkerEvent::Failed(e.to_string()),
+            };
+            reporter.send(event);
+        });
+
+        Self { rx, cancel }
+    }
+
+    /// Pide al hilo que se detenga en cuanto pueda
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelling(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Mensajes pendientes, sin bloquear
+    pub fn poll(&self) -> Vec<WorkerEvent> {
+        self.rx.try_iter().collect()
+    }
+
+    /// Espera al siguiente mensaje; `None` cuando el hilo ya terminó y no queda ninguno
+    pub fn recv(&self) -> Option<WorkerEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+struct Reporter {
+    tx: Sender<WorkerEvent>,
+    notify: Option<Box<dyn Fn() + Send>>,
+}
+
+impl Reporter {
+    fn send(&self, event: WorkerEvent) {
+        let _ = self.tx.send(event);
+        if let Some(notify) = &self.notify {
+            notify();
+        }
+    }
+
+    fn progress(&self, label: &str, done: u64, total: u64) {
+        self.send(WorkerEvent::Progress { label: label.to_string(), done, total });
+    }
+}
+
+fn run(db_path: &str, task: Task, cancel: &AtomicBool, reporter: &Reporter) -> Result<WorkerEvent> {
+    match task {
+        Task::Scan { root, hash_contents } => {
+            let db = Database::open(db_path)?;
+            let categories: Vec<String> = db.categories().get_categories()?.into_iter().map(|c| c.path).collect();
+            let summary = scanner::scan_library(&root, &db.files(), &categories, hash_contents, cancel, &mut |done, total, name| {
+                reporter.progress(&format!("Indexando {}", name), done as u64, total as u64);
+            })?;
+            if summary.cancelled {
+                return Ok(WorkerEvent::Cancelled);
+            }
+            Ok(WorkerEvent::ScanFinished(summary))
+        }
+        Task::ImportItem { mut item, root, data_srcs, image_src, use_package_preview, collision, changelog } => {
+            item.name = item.name.trim().to_string();
+            if !scanner::is_valid_folder_name(&item.name) {
+                bail!("nombre de item no válido: '{}'", item.name);
+            }
+            let item_dir = scanner::category_dir(&root, &item.category).join(&item.name);
+            let new_dir = !item_dir.exists();
+            fs::create_dir_all(&item_dir)?;
+
+            // Archivos que no existían antes; si se cancela o algo falla antes de guardar el item solo
+            // se borran estos (y la carpeta si también es nueva), nunca uno que se estaba sobrescribiendo
+            let mut created: Vec<PathBuf> = Vec::new();
+            let item_path = item_dir.display().to_string();
+            // Copia los archivos y guarda el item; `None` si se canceló
+            let mut save = || -> Result<Option<Database>> {
+                // (ruta guardada, papel) de cada archivo del item
+                let mut stored: Vec<(PathBuf, FileRole)> = Vec::new();
+
+                for src in &data_srcs {
+                    let Some(file_name) = src.file_name() else { continue };
+                    let (dst, copy) = collision.resolve(&item_dir, file_name);
+                    if copy {
+                        let existed = dst.exists();
+                        if !copy_with_progress(src, &dst, cancel, reporter)? {
+                            return Ok(None);
+                        }
+                        if !existed {
+                            created.push(dst.clone());
+                        }
+                    }
+                    stored.push((dst, FileRole::detect(src)));
+                }
+
+                let mut image_path = None;
+                if use_package_preview {
+                    let package = stored.iter().find(|(p, _)| unitypackage::is_unitypackage(p));
+                    if let Some((package, _)) = package {
+                        reporter.progress("Buscando vista previa del paquete", 0, 0);
+                        match unitypackage::extract_preview(package) {
+                            Ok(Some(bytes)) => {
+                                let (dst, write) = collision.resolve(&item_dir, OsStr::new("preview.png"));
+                                if write {
+                                    if !dst.exists() {
+                                        created.push(dst.clone());
+                                    }
+                                    fs::write(&dst, bytes)?;
+                                }
+                                image_path = Some(dst);
+                            }
+                            Ok(None) => {}
+                            Err(e) => reporter.send(WorkerEvent::Warning(format!(
+                                "Error leyendo vista previa de {}: {}",
+                                package.display(),
+                                e
+                            ))),
+                        }
+                    }
+                }
+                if image_path.is_none() && let Some(src) = &image_src && let Some(file_name) = src.file_name() {
+                    let (dst, copy) = collision.resolve(&item_dir, file_name);
+                    if copy {
+                        let existed = dst.exists();
+                        if !copy_with_progress(src, &dst, cancel, reporter)? {
+                            return Ok(None);
+                        }
+                        if !existed {
+                            created.push(dst.clone());
+                        }
+                    }
+                    image_path = Some(dst);
+                }
+                if let Some(image) = &image_path {
+                    stored.push((image.clone(), FileRole::Image));
+                }
+
+                item.file_path = stored.first().map(|(p, _)| p.display().to_string()).unwrap_or_default();
+                item.image_path = image_path.map(|p| p.display().to_string());
+
+                // El item, sus archivos, sus tags y la carpeta indexada se guardan juntos o nada
+                let meta = scanner::folder_metadata(&item_dir);
+                let db = Database::open(db_path)?;
+                let imported_at = chrono::Utc::now().timestamp();
+                db.transaction(|db| {
+                    // Otra versión de un item que ya existe: se actualiza en vez de crear uno nuevo
+                    let existing = match db.items().find_item_in_folder(&item_path)? {
+                        Some(id) => db.items().get_item(id)?,
+                        None => None,
+                    };
+                    let mut folder_tags = item.tags.clone();
+                    match existing {
+                        Some(existing) => {
+                            item.id = existing.id;
+                            item.image_path = item.image_path.take().or(existing.image_path);
+                            item.notes = item.notes.take().or(existing.notes);
+                            for tag in existing.tags {
+                                if !item.tags.contains(&tag) {
+                                    item.tags.push(tag);
+                                }
+                            }
+                            db.items().update_item(&item)?;
+                            if let Some(folder) = db.files().get_file(&item_path)? {
+                                folder_tags = folder.tags;
+                                for tag in &item.tags {
+                                    if !folder_tags.contains(tag) {
+                                        folder_tags.push(tag.clone());
+                                    }
+                                }
+                            }
+                        }
+                        None => item.id = db.items().insert_item(&item)?,
+                    }
+                    for (path, role) in &stored {
+                        db.items().add_item_file(item.id, &path.display().to_string(), *role)?;
+                    }
+                    // Indexar la carpeta del item para que aparezca en la búsqueda
+                    db.files().insert_file(&IndexedFile {
+                        path: item_path.clone(),
+                        name: item.name.clone(),
+                        tags: folder_tags,
+                        size: meta.size,
+                        mtime: meta.mtime,
+                        hash: None,
+                        version: None,
+                    })?;
+                    for (path, _) in stored.iter().filter(|(p, _)| versions::is_release(p)) {
+                        let Some(version) = versions::detect(path) else { continue };
+                        db.files().add_version(&ItemVersion {
+                            item_path: item_path.clone(),
+                            file_path: path.display().to_string(),
+                            version,
+                            imported_at,
+                            changelog: changelog.clone(),
+                            ..Default::default()
+                        })?;
+                    }
+                    Ok(())
+                })?;
+                Ok(Some(db))
+            };
+            let db = match save() {
+                Ok(Some(db)) => db,
+                result => {
+                    for path in &created {
+                        let _ = fs::remove_file(path);
+                    }
+                    if new_dir {
+                        let _ = fs::remove_dir(&item_dir);
+                    }
+                    return result.map(|_| WorkerEvent::Cancelled);
+                }
+            };
+
+            reporter.progress("Leyendo contenido de paquetes", 0, 0);
+            let mut warnings = Vec::new();
+            scanner::index_packages(&db.files(), &item_path, &item_dir, &mut warnings)?;
+            for warning in warnings {
+                reporter.send(WorkerEvent::Warning(warning));
+            }
+
+            Ok(WorkerEvent::ItemImported)
+        }
+        Task::FindDuplicates => {
+            let db = Database::open(db_path)?;
+            let groups = dedup::find_duplicates(&db.files(), cancel, &mut |done, total, name| {
+                reporter.progress(&format!("Calculando hash de {}", name), done, total);
+            })?;
+            match groups {
+                Some(groups) => Ok(WorkerEvent::DuplicatesFound(groups)),
+                None => Ok(WorkerEvent::Cancelled),
+            }
+        }
+    }
+}
+
+/// Copia `src` a `dst` en bloques informando el progreso. Devuelve `false` si se canceló.
+///
+/// Se copia a un archivo temporal junto a `dst` que solo reemplaza a `dst` al terminar, así que si
+/// se cancela o falla `dst` queda como estaba.
+fn copy_with_progress(src: &Path, dst: &Path, cancel: &AtomicBool, reporter: &Reporter) -> Result<bool> {
+    let name = dst.file_name().unwrap_or_default().to_string_lossy();
+    let part = dst.with_file_name(format!(".{}.part", name));
+    let result = copy_to(src, &part, cancel, reporter).and_then(|copied| {
+        if copied {
+            fs::rename(&part, dst)?;
+        }
+        Ok(copied)
+    });
+    if !matches!(result, Ok(true)) {
+        let _ = fs::remove_file(&part);
+    }
+    result
+}
+
+fn copy_to(src: &Path, dst: &Path, cancel: &AtomicBool, reporter: &Reporter) -> Result<bool> {
+    let total = fs::metadata(src)?.len();
+    let label = format!("Copiando {}", src.file_name().unwrap_or_default().to_string_lossy());
+
+    let mut reader = File::open(src)?;
+    let mut writer = File::create(dst)?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut done = 0u64;
+
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n])?;
+        done += n as u64;
+        reporter.progress(&label, done, total);
+    }
+    writer.sync_all()?;
+    Ok(true)
+}